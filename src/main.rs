@@ -3,14 +3,18 @@ mod output;
 mod signal;
 mod synths;
 mod util;
+mod wav;
 
 use midi::*;
 use signal::{
-  combinations::*, compress::*, core::*, envelope::*, filter::*, math::*,
-  midi::*, modulation::*, osc::*, sequence::*, shaping::*, *,
+  chorus::*, combinations::*, compress::*, convolution::*, core::*, delay::*,
+  dynamics::*, envelope::*, eq::*, filter::*, formant::*, granular::*, lfo::*,
+  lofi::*, math::*, midi::*, modulation::*, osc::*, oversample::*, pitch::*,
+  reverb::*, sequence::*, shaping::*, slew::*, vocoder::*, *,
 };
 use synths::*;
 use util::*;
+use wav::*;
 
 fn main() {
   output::begin(Box::new(Sigmoid(
@@ -18,7 +22,7 @@ fn main() {
       * (1.
         * (AttackExpDecay::new(
           Const(0.025),
-          Const(15.),
+          Const(1. / 15.),
           GateSeq::new(1., vec![0., (1. / 3.), (2. / 3.), (5. / 6.)]),
         ) * OnePoleLowPass::new(
           Const(0.2),
@@ -53,7 +57,7 @@ fn main() {
             ),
             /*AttackExpDecay::new(
               0.001,
-              12.,
+              1. / 12.,
               MidiNote::new(note_index, &midi.ledger),
             ),*/
           );
@@ -84,7 +88,7 @@ fn main() {
                  ),
                ),
              ),
-             AttackExpDecay::new(Const(0.001), Const(24.), Beat::new(0.5, 0.)),
+             AttackExpDecay::constant(0.001, 1. / 24., Beat::new(0.5, 0.)),
            ),
          EnvelopedVoiceAllocator::new(
            8,
@@ -101,7 +105,7 @@ fn main() {
                  ),
                  /*AttackExpDecay::new(
                    0.001,
-                   12.,
+                   1. / 12.,
                    MidiNote::new(note_index, &midi.ledger),
                  ),*/
                );
@@ -8,36 +8,138 @@ use std::{
 
 use midir::{Ignore, MidiInput, MidiInputConnection};
 
+// Channels are numbered from 0 to 15 and 7-bit values are scaled from 0 to 1,
+// except for the raw controller values of `ControlChange`. System messages
+// aren't tied to a channel.
 #[derive(Debug, Clone)]
 pub enum MidiEvent {
-  NoteDown(u8),
-  NoteUp(u8),
-  ModWheel(f64),
-  PitchBend(f64),
+  // channel, note, velocity
+  NoteDown(u8, u8, f64),
+  // channel, note, release velocity
+  NoteUp(u8, u8, f64),
+  // channel, controller number, value
+  ControlChange(u8, u8, u8),
+  // channel, bend from -1 to 1
+  PitchBend(u8, f64),
+  // channel, pressure
+  ChannelPressure(u8, f64),
+  // channel, note, pressure
+  PolyPressure(u8, u8, f64),
+  // channel, program
+  ProgramChange(u8, u8),
+  SysEx(Vec<u8>),
+  Clock,
+  Start,
+  Continue,
+  Stop,
+  ActiveSensing,
+  Reset,
 }
 
 impl MidiEvent {
+  // Parses the first complete event in `bytes`, without any running status
+  // from earlier messages.
   pub fn from_message(bytes: &[u8]) -> Option<Self> {
-    if bytes.len() == 3 {
-      let status = bytes[0];
-      let command = status >> 4;
-      let channel = status & 0x0F;
-      let note = bytes[1];
-      match command {
-        14 => Some(Self::PitchBend(((bytes[2] as f64) / 127.) * 2. - 1.)),
-        11 => Some(Self::ModWheel(bytes[2] as f64 / 127.)),
-        9 => Some(Self::NoteDown(note)),
-        8 => Some(Self::NoteUp(note)),
-        _ => {
-          println!(
-            "unrecognized midi message: command = {:?}, bytes = {:?}",
-            command, bytes
-          );
-          None
+    MidiParser::new().parse(bytes).into_iter().next()
+  }
+}
+
+fn data_length(status: u8) -> usize {
+  match status {
+    0x80..=0xBF | 0xE0..=0xEF | 0xF2 => 2,
+    0xC0..=0xDF | 0xF1 | 0xF3 => 1,
+    _ => 0,
+  }
+}
+
+fn unit(value: u8) -> f64 {
+  value as f64 / 127.
+}
+
+// A MIDI 1.0 byte stream parser. It keeps running status between messages,
+// collects system exclusive data until its end byte, and lets real-time
+// messages interrupt anything else.
+#[derive(Debug, Default)]
+pub struct MidiParser {
+  status: Option<u8>,
+  data: Vec<u8>,
+  sysex: Option<Vec<u8>>,
+}
+
+impl MidiParser {
+  pub fn new() -> Self {
+    Self::default()
+  }
+  pub fn parse(&mut self, bytes: &[u8]) -> Vec<MidiEvent> {
+    bytes.iter().filter_map(|&byte| self.push(byte)).collect()
+  }
+  pub fn push(&mut self, byte: u8) -> Option<MidiEvent> {
+    match byte {
+      0xF8 => Some(MidiEvent::Clock),
+      0xFA => Some(MidiEvent::Start),
+      0xFB => Some(MidiEvent::Continue),
+      0xFC => Some(MidiEvent::Stop),
+      0xFE => Some(MidiEvent::ActiveSensing),
+      0xFF => Some(MidiEvent::Reset),
+      0xF9 | 0xFD => None,
+      0xF0 => {
+        self.status = None;
+        self.sysex = Some(vec![]);
+        None
+      }
+      0xF7 => self.sysex.take().map(MidiEvent::SysEx),
+      0x80..=0xF6 => {
+        // any other status byte also ends an unterminated sysex message
+        self.sysex = None;
+        self.data.clear();
+        self.status = Some(byte);
+        None
+      }
+      _ => {
+        if let Some(sysex) = &mut self.sysex {
+          sysex.push(byte);
+          return None;
+        }
+        let status = self.status?;
+        self.data.push(byte);
+        if self.data.len() < data_length(status) {
+          return None;
         }
+        let event = Self::decode(status, &self.data);
+        self.data.clear();
+        if status >= 0xF0 {
+          // system common messages don't set running status
+          self.status = None;
+        }
+        event
       }
-    } else {
-      None
+    }
+  }
+  fn decode(status: u8, data: &[u8]) -> Option<MidiEvent> {
+    let channel = status & 0x0F;
+    match status >> 4 {
+      0x8 => Some(MidiEvent::NoteUp(channel, data[0], unit(data[1]))),
+      0x9 => Some(if data[1] == 0 {
+        MidiEvent::NoteUp(channel, data[0], 0.)
+      } else {
+        MidiEvent::NoteDown(channel, data[0], unit(data[1]))
+      }),
+      0xA => Some(MidiEvent::PolyPressure(channel, data[0], unit(data[1]))),
+      0xB => Some(MidiEvent::ControlChange(channel, data[0], data[1])),
+      0xC => Some(MidiEvent::ProgramChange(channel, data[0])),
+      0xD => Some(MidiEvent::ChannelPressure(channel, unit(data[0]))),
+      0xE => {
+        let bend = ((data[1] as i32) << 7 | data[0] as i32) - 8192;
+        Some(MidiEvent::PitchBend(
+          channel,
+          if bend < 0 {
+            bend as f64 / 8192.
+          } else {
+            bend as f64 / 8191.
+          },
+        ))
+      }
+      _ => None,
     }
   }
 }
@@ -45,13 +147,23 @@ impl MidiEvent {
 #[derive(Debug)]
 pub struct NoteState {
   pub down: bool,
+  pub velocity: f64,
 }
 
+const MOD_WHEEL: u8 = 1;
+// Controllers 0 to 31 can be paired with controllers 32 to 63 as their least
+// significant bytes, for 14-bit resolution.
+const LSB_OFFSET: u8 = 32;
+
 #[derive(Debug)]
 pub struct MidiLedger {
   pub notes: HashMap<u8, NoteState>,
   pub mod_wheel: f64,
   pub pitch_bend: f64,
+  // raw controller values for each channel
+  pub controllers: [[u8; 128]; 16],
+  // whether each paired controller has been sent a least significant byte
+  high_resolution: [[bool; 32]; 16],
 }
 
 impl MidiLedger {
@@ -60,11 +172,41 @@ impl MidiLedger {
       notes: HashMap::new(),
       mod_wheel: 0.5,
       pitch_bend: 0.,
+      controllers: [[0; 128]; 16],
+      high_resolution: [[false; 32]; 16],
     }
   }
   pub fn create() -> Arc<Mutex<Self>> {
     Arc::new(Mutex::new(Self::new()))
   }
+  pub fn set_controller(&mut self, channel: u8, controller: u8, value: u8) {
+    let (channel, controller) = (channel as usize & 0x0F, controller & 0x7F);
+    self.controllers[channel][controller as usize] = value & 0x7F;
+    if controller < LSB_OFFSET {
+      // a new most significant byte resets the least significant one
+      self.controllers[channel][(controller + LSB_OFFSET) as usize] = 0;
+    } else if controller < 2 * LSB_OFFSET {
+      self.high_resolution[channel][(controller - LSB_OFFSET) as usize] = true;
+    }
+    if controller == MOD_WHEEL || controller == MOD_WHEEL + LSB_OFFSET {
+      self.mod_wheel = self.controller(channel as u8, MOD_WHEEL);
+    }
+  }
+  // The value of a controller from 0 to 1. Controllers 0 to 31 include their
+  // least significant byte once one has been received.
+  pub fn controller(&self, channel: u8, controller: u8) -> f64 {
+    let (channel, controller) = (channel as usize & 0x0F, controller & 0x7F);
+    let msb = self.controllers[channel][controller as usize] as f64;
+    if controller < LSB_OFFSET
+      && self.high_resolution[channel][controller as usize]
+    {
+      let lsb =
+        self.controllers[channel][(controller + LSB_OFFSET) as usize] as f64;
+      (msb * 128. + lsb) / 16383.
+    } else {
+      msb / 127.
+    }
+  }
 }
 
 pub struct MidiListener {
@@ -107,28 +249,41 @@ impl MidiListener {
     println!("Opening midi connection...");
     let in_port_name = midi_in.port_name(in_port)?;
 
+    let mut parser = MidiParser::new();
     let input_connection = midi_in
       .connect(
         in_port,
         "midir-read-input",
-        |_timestamp, message, ledger_mutex| {
-          if let Some(event) = MidiEvent::from_message(message) {
-            let mut ledger = ledger_mutex.lock().unwrap();
+        move |_timestamp, message, ledger_mutex| {
+          let events = parser.parse(message);
+          if events.is_empty() {
+            return;
+          }
+          let mut ledger = ledger_mutex.lock().unwrap();
+          for event in events {
             match event {
-              MidiEvent::NoteDown(note) => {
-                ledger.notes.insert(note, NoteState { down: true });
+              MidiEvent::NoteDown(_, note, velocity) => {
+                ledger.notes.insert(
+                  note,
+                  NoteState {
+                    down: true,
+                    velocity,
+                  },
+                );
               }
-              MidiEvent::NoteUp(note) => {
-                ledger.notes.insert(note, NoteState { down: false });
+              MidiEvent::NoteUp(_, note, _) => {
+                if let Some(state) = ledger.notes.get_mut(&note) {
+                  state.down = false;
+                }
               }
-              MidiEvent::ModWheel(value) => {
-                ledger.mod_wheel = value;
+              MidiEvent::ControlChange(channel, controller, value) => {
+                ledger.set_controller(channel, controller, value);
               }
-              MidiEvent::PitchBend(value) => {
+              MidiEvent::PitchBend(_, value) => {
                 ledger.pitch_bend = value;
               }
+              _ => {}
             }
-            println!("{:?}: {:?}", event, message);
           }
         },
         ledger.clone(),
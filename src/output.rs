@@ -1,11 +1,25 @@
-use crate::signal::Signal;
+use crate::signal::{Signal, StereoSignal};
 use anyhow::{Error, Result};
 use cpal::{
   traits::{DeviceTrait, HostTrait, StreamTrait},
   FromSample, Sample, SizedSample,
 };
 
-pub fn begin(signal: Box<dyn Signal>) -> Result<()> {
+pub fn begin(mut signal: Box<dyn Signal>) -> Result<()> {
+  begin_frames(move |t| {
+    let value = signal.sample(t);
+    (value, value)
+  })
+}
+
+pub fn begin_stereo(mut signal: Box<dyn StereoSignal>) -> Result<()> {
+  begin_frames(move |t| signal.sample_stereo(t))
+}
+
+fn begin_frames<F>(next_frame: F) -> Result<()>
+where
+  F: FnMut(f64) -> (f64, f64) + Send + 'static,
+{
   let host = cpal::default_host();
 
   let device = host
@@ -17,31 +31,48 @@ pub fn begin(signal: Box<dyn Signal>) -> Result<()> {
   //println!("Default output config: {:?}", config);
 
   match config.sample_format() {
-    cpal::SampleFormat::I8 => run::<i8>(&device, &config.into(), signal),
-    cpal::SampleFormat::I16 => run::<i16>(&device, &config.into(), signal),
+    cpal::SampleFormat::I8 => run::<i8, F>(&device, &config.into(), next_frame),
+    cpal::SampleFormat::I16 => {
+      run::<i16, F>(&device, &config.into(), next_frame)
+    }
     // cpal::SampleFormat::I24 => run::<I24>(&device, &config.into()),
-    cpal::SampleFormat::I32 => run::<i32>(&device, &config.into(), signal),
+    cpal::SampleFormat::I32 => {
+      run::<i32, F>(&device, &config.into(), next_frame)
+    }
     // cpal::SampleFormat::I48 => run::<I48>(&device, &config.into()),
-    cpal::SampleFormat::I64 => run::<i64>(&device, &config.into(), signal),
-    cpal::SampleFormat::U8 => run::<u8>(&device, &config.into(), signal),
-    cpal::SampleFormat::U16 => run::<u16>(&device, &config.into(), signal),
+    cpal::SampleFormat::I64 => {
+      run::<i64, F>(&device, &config.into(), next_frame)
+    }
+    cpal::SampleFormat::U8 => run::<u8, F>(&device, &config.into(), next_frame),
+    cpal::SampleFormat::U16 => {
+      run::<u16, F>(&device, &config.into(), next_frame)
+    }
     // cpal::SampleFormat::U24 => run::<U24>(&device, &config.into()),
-    cpal::SampleFormat::U32 => run::<u32>(&device, &config.into(), signal),
+    cpal::SampleFormat::U32 => {
+      run::<u32, F>(&device, &config.into(), next_frame)
+    }
     // cpal::SampleFormat::U48 => run::<U48>(&device, &config.into()),
-    cpal::SampleFormat::U64 => run::<u64>(&device, &config.into(), signal),
-    cpal::SampleFormat::F32 => run::<f32>(&device, &config.into(), signal),
-    cpal::SampleFormat::F64 => run::<f64>(&device, &config.into(), signal),
+    cpal::SampleFormat::U64 => {
+      run::<u64, F>(&device, &config.into(), next_frame)
+    }
+    cpal::SampleFormat::F32 => {
+      run::<f32, F>(&device, &config.into(), next_frame)
+    }
+    cpal::SampleFormat::F64 => {
+      run::<f64, F>(&device, &config.into(), next_frame)
+    }
     sample_format => panic!("Unsupported sample format '{sample_format}'"),
   }
 }
 
-pub fn run<T>(
+pub fn run<T, F>(
   device: &cpal::Device,
   config: &cpal::StreamConfig,
-  mut signal: Box<dyn Signal>,
+  mut next_frame: F,
 ) -> Result<()>
 where
   T: SizedSample + FromSample<f32>,
+  F: FnMut(f64) -> (f64, f64) + Send + 'static,
 {
   let sample_rate = config.sample_rate.0 as f64;
   let channels = config.channels as usize;
@@ -49,7 +80,8 @@ where
   let mut sample_counter = 0f64;
   let mut next_value = move || {
     sample_counter = sample_counter + 1.0;
-    signal.sample(sample_counter / sample_rate) as f32
+    let (left, right) = next_frame(sample_counter / sample_rate);
+    (left as f32, right as f32)
   };
 
   let err_fn = |err| eprintln!("an error occurred on stream: {}", err);
@@ -74,14 +106,18 @@ where
 fn write_data<T>(
   output: &mut [T],
   channels: usize,
-  next_sample: &mut dyn FnMut() -> f32,
+  next_sample: &mut dyn FnMut() -> (f32, f32),
 ) where
   T: Sample + FromSample<f32>,
 {
   for frame in output.chunks_mut(channels) {
-    let value: T = T::from_sample(next_sample());
-    for sample in frame.iter_mut() {
-      *sample = value;
+    let (left, right) = next_sample();
+    if frame.len() == 1 {
+      frame[0] = T::from_sample(0.5 * (left + right));
+    } else {
+      for (channel, sample) in frame.iter_mut().enumerate() {
+        *sample = T::from_sample(if channel % 2 == 0 { left } else { right });
+      }
     }
   }
 }
@@ -1,22 +1,50 @@
 use std::sync::{Arc, Mutex};
 
+pub mod chorus;
 pub mod combinations;
 pub mod compress;
+pub mod convolution;
 pub mod core;
+pub mod delay;
+pub mod dynamics;
 pub mod envelope;
+pub mod eq;
 pub mod filter;
+pub mod formant;
+pub mod granular;
+pub mod lfo;
+pub mod lofi;
 pub mod math;
 pub mod midi;
 pub mod modulation;
 pub mod osc;
+pub mod oversample;
+pub mod pitch;
+pub mod reverb;
 pub mod sequence;
 pub mod shaping;
+pub mod slew;
+pub mod vocoder;
 pub mod waveguide;
 
 pub trait Signal: Send {
   fn sample(&mut self, t: f64) -> f64;
 }
 
+pub trait StereoSignal: Send {
+  fn sample_stereo(&mut self, t: f64) -> (f64, f64);
+}
+
+pub fn render<S: Signal>(
+  signal: &mut S,
+  duration: f64,
+  sample_rate: f64,
+) -> Vec<f64> {
+  (1..=(duration * sample_rate).round() as usize)
+    .map(|i| signal.sample(i as f64 / sample_rate))
+    .collect()
+}
+
 pub struct Pure<F: FnMut(f64) -> f64>(pub F);
 //derive_signal_ops!(Pure<F: FnMut(f64) -> f64>);
 impl<F: FnMut(f64) -> f64 + std::marker::Send> Signal for Pure<F> {
@@ -0,0 +1,233 @@
+use crate::util::{mix, SampleClock};
+use crate::{derive_signal_ops, DelayInterpolation, DelayLine, Signal};
+
+const TAU: f64 = 2.0 * std::f64::consts::PI;
+
+// Rate is in Hz and depth runs from 0 to 1 for all of these effects, so they
+// can be driven directly by a clock-derived signal or a MIDI control.
+
+pub struct Chorus<R: Signal, D: Signal, S: Signal> {
+  rate: R,
+  depth: D,
+  child: S,
+  line: DelayLine,
+  voices: usize,
+  base_delay: f64,
+  sweep: f64,
+  spread: f64,
+  wet: f64,
+  phase: f64,
+  clock: SampleClock,
+}
+derive_signal_ops!(Chorus<R: Signal, D: Signal, S: Signal>);
+impl<R: Signal, D: Signal, S: Signal> Chorus<R, D, S> {
+  pub fn new(voices: usize, rate: R, depth: D, child: S) -> Self {
+    Self {
+      rate,
+      depth,
+      child,
+      line: DelayLine::new(0.1),
+      voices: voices.max(1),
+      base_delay: 0.012,
+      sweep: 0.006,
+      spread: 0.008,
+      wet: 0.5,
+      phase: 0.,
+      clock: SampleClock::new(),
+    }
+  }
+  pub fn with_base_delay(mut self, base_delay: f64) -> Self {
+    self.base_delay = base_delay;
+    self
+  }
+  pub fn with_sweep(mut self, sweep: f64) -> Self {
+    self.sweep = sweep;
+    self
+  }
+  // spreads the voices' base delays evenly across `spread` seconds
+  pub fn with_spread(mut self, spread: f64) -> Self {
+    self.spread = spread;
+    self
+  }
+  pub fn with_mix(mut self, wet: f64) -> Self {
+    self.wet = wet;
+    self
+  }
+}
+impl<R: Signal, D: Signal, S: Signal> Signal for Chorus<R, D, S> {
+  fn sample(&mut self, t: f64) -> f64 {
+    let dt = self.clock.tick(t);
+    let sample_rate = 1. / dt;
+    self.phase = (self.phase + self.rate.sample(t) * dt) % 1.;
+    let depth = self.depth.sample(t).clamp(0., 1.);
+    let input = self.child.sample(t);
+    let voices = self.voices as f64;
+    let spread_step = if self.voices > 1 {
+      self.spread / (voices - 1.)
+    } else {
+      0.
+    };
+    let wet = (0..self.voices)
+      .map(|voice| {
+        let voice = voice as f64;
+        let lfo = (TAU * (self.phase + voice / voices)).sin();
+        let delay = self.base_delay
+          + spread_step * voice
+          + self.sweep * depth * (0.5 + 0.5 * lfo);
+        self
+          .line
+          .read(delay * sample_rate, DelayInterpolation::Linear)
+      })
+      .sum::<f64>()
+      / voices;
+    self.line.push(input, sample_rate);
+    mix(input, wet, self.wet)
+  }
+}
+
+pub struct Flanger<R: Signal, D: Signal, S: Signal> {
+  rate: R,
+  depth: D,
+  child: S,
+  line: DelayLine,
+  max_delay: f64,
+  feedback: f64,
+  through_zero: bool,
+  // holds the clean input for the delayed dry path of through-zero flanging
+  dry_line: DelayLine,
+  wet: f64,
+  phase: f64,
+  clock: SampleClock,
+}
+derive_signal_ops!(Flanger<R: Signal, D: Signal, S: Signal>);
+impl<R: Signal, D: Signal, S: Signal> Flanger<R, D, S> {
+  pub fn new(rate: R, depth: D, child: S) -> Self {
+    Self {
+      rate,
+      depth,
+      child,
+      line: DelayLine::new(0.01),
+      max_delay: 0.005,
+      feedback: 0.,
+      through_zero: false,
+      dry_line: DelayLine::new(0.01),
+      wet: 0.5,
+      phase: 0.,
+      clock: SampleClock::new(),
+    }
+  }
+  pub fn with_max_delay(mut self, max_delay: f64) -> Self {
+    self.max_delay = max_delay;
+    self.line = DelayLine::new(2. * max_delay);
+    self.dry_line = DelayLine::new(2. * max_delay);
+    self
+  }
+  pub fn with_feedback(mut self, feedback: f64) -> Self {
+    self.feedback = feedback;
+    self
+  }
+  // Delays the dry path by `max_delay` so that the swept path can cross it,
+  // cancelling completely at the zero point like tape flanging.
+  pub fn through_zero(mut self) -> Self {
+    self.through_zero = true;
+    self
+  }
+  pub fn with_mix(mut self, wet: f64) -> Self {
+    self.wet = wet;
+    self
+  }
+}
+impl<R: Signal, D: Signal, S: Signal> Signal for Flanger<R, D, S> {
+  fn sample(&mut self, t: f64) -> f64 {
+    let dt = self.clock.tick(t);
+    let sample_rate = 1. / dt;
+    self.phase = (self.phase + self.rate.sample(t) * dt) % 1.;
+    let depth = self.depth.sample(t).clamp(0., 1.);
+    let lfo = (TAU * self.phase).sin();
+    let input = self.child.sample(t);
+    let (dry, delay) = if self.through_zero {
+      let dry = self
+        .dry_line
+        .read(self.max_delay * sample_rate, DelayInterpolation::Linear);
+      self.dry_line.push(input, sample_rate);
+      (dry, self.max_delay * (1. + depth * lfo))
+    } else {
+      (input, self.max_delay * depth * (0.5 + 0.5 * lfo))
+    };
+    let wet = self
+      .line
+      .read(delay * sample_rate, DelayInterpolation::Linear);
+    self.line.push(input + self.feedback * wet, sample_rate);
+    mix(dry, wet, self.wet)
+  }
+}
+
+pub struct Phaser<R: Signal, D: Signal, S: Signal> {
+  rate: R,
+  depth: D,
+  child: S,
+  stages: Vec<(f64, f64)>,
+  min_frequency: f64,
+  max_frequency: f64,
+  feedback: f64,
+  wet: f64,
+  last_output: f64,
+  phase: f64,
+  clock: SampleClock,
+}
+derive_signal_ops!(Phaser<R: Signal, D: Signal, S: Signal>);
+impl<R: Signal, D: Signal, S: Signal> Phaser<R, D, S> {
+  pub fn new(stages: usize, rate: R, depth: D, child: S) -> Self {
+    Self {
+      rate,
+      depth,
+      child,
+      stages: vec![(0., 0.); stages],
+      min_frequency: 200.,
+      max_frequency: 4000.,
+      feedback: 0.,
+      wet: 0.5,
+      last_output: 0.,
+      phase: 0.,
+      clock: SampleClock::new(),
+    }
+  }
+  pub fn with_range(mut self, min_frequency: f64, max_frequency: f64) -> Self {
+    self.min_frequency = min_frequency;
+    self.max_frequency = max_frequency;
+    self
+  }
+  pub fn with_feedback(mut self, feedback: f64) -> Self {
+    self.feedback = feedback;
+    self
+  }
+  pub fn with_mix(mut self, wet: f64) -> Self {
+    self.wet = wet;
+    self
+  }
+}
+impl<R: Signal, D: Signal, S: Signal> Signal for Phaser<R, D, S> {
+  fn sample(&mut self, t: f64) -> f64 {
+    let dt = self.clock.tick(t);
+    self.phase = (self.phase + self.rate.sample(t) * dt) % 1.;
+    let depth = self.depth.sample(t).clamp(0., 1.);
+    let sweep = depth * (0.5 + 0.5 * (TAU * self.phase).sin());
+    let frequency = (self.min_frequency
+      * (self.max_frequency / self.min_frequency).powf(sweep))
+    .min(0.49 / dt);
+    let tan = (std::f64::consts::PI * frequency * dt).tan();
+    let a = (tan - 1.) / (tan + 1.);
+    let input = self.child.sample(t);
+    let output = self.stages.iter_mut().fold(
+      input + self.feedback * self.last_output,
+      |x, (last_x, last_y)| {
+        let y = a * x + *last_x - a * *last_y;
+        *last_x = x;
+        *last_y = y;
+        y
+      },
+    );
+    self.last_output = output;
+    mix(input, output, self.wet)
+  }
+}
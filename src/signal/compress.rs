@@ -1,4 +1,4 @@
-use crate::{derive_signal_ops, Signal};
+use crate::{derive_signal_ops, AdaaState, Signal};
 
 pub fn sigmoid(x: f64) -> f64 {
   1.0 / (1.0 + (-x).exp())
@@ -28,3 +28,63 @@ impl<C: Signal, S: Signal> Signal for Clamp<C, S> {
     self.1.sample(t).max(-c).min(c)
   }
 }
+
+fn log_cosh(x: f64) -> f64 {
+  x.abs() + (-2. * x.abs()).exp().ln_1p() - std::f64::consts::LN_2
+}
+
+pub struct SigmoidAdaa<S: Signal>(pub S, AdaaState);
+derive_signal_ops!(SigmoidAdaa<S: Signal>);
+impl<S: Signal> SigmoidAdaa<S> {
+  pub fn new(child: S) -> Self {
+    Self(child, AdaaState::default())
+  }
+}
+impl<S: Signal> Signal for SigmoidAdaa<S> {
+  fn sample(&mut self, t: f64) -> f64 {
+    // 2 * sigmoid(x) - 1 is tanh(x / 2)
+    let x = self.0.sample(t);
+    self
+      .1
+      .process(x, |x| (0.5 * x).tanh(), |x| 2. * log_cosh(0.5 * x))
+  }
+}
+
+pub struct TanhAdaa<S: Signal>(pub S, AdaaState);
+derive_signal_ops!(TanhAdaa<S: Signal>);
+impl<S: Signal> TanhAdaa<S> {
+  pub fn new(child: S) -> Self {
+    Self(child, AdaaState::default())
+  }
+}
+impl<S: Signal> Signal for TanhAdaa<S> {
+  fn sample(&mut self, t: f64) -> f64 {
+    let x = self.0.sample(t);
+    self.1.process(x, f64::tanh, log_cosh)
+  }
+}
+
+pub struct ClampAdaa<C: Signal, S: Signal>(pub C, pub S, AdaaState);
+derive_signal_ops!(ClampAdaa<C: Signal, S: Signal>);
+impl<C: Signal, S: Signal> ClampAdaa<C, S> {
+  pub fn new(c: C, child: S) -> Self {
+    Self(c, child, AdaaState::default())
+  }
+}
+impl<C: Signal, S: Signal> Signal for ClampAdaa<C, S> {
+  fn sample(&mut self, t: f64) -> f64 {
+    let c = self.0.sample(t);
+    let x = self.1.sample(t);
+    self.2.process(
+      x,
+      |x| x.max(-c).min(c),
+      |x| {
+        if x.abs() <= c {
+          0.5 * x * x
+        } else {
+          c * x.abs() - 0.5 * c * c
+        }
+      },
+    )
+  }
+}
@@ -0,0 +1,290 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::Result;
+use rustfft::{num_complex::Complex, Fft, FftPlanner};
+
+use crate::util::{mix, resample, SampleClock};
+use crate::wav::load_wav;
+use crate::{derive_signal_ops, DelayInterpolation, DelayLine, Signal};
+
+#[derive(Debug, Clone, Copy)]
+pub enum ConvolutionPartitioning {
+  Uniform(usize),
+  // Starts with `min_block` sized partitions for low latency, then grows the
+  // partition size by 4x for each later stretch of the impulse response, up
+  // to `max_block`.
+  NonUniform { min_block: usize, max_block: usize },
+}
+
+// Uniformly partitioned overlap-save convolution, with a latency of `block`
+// samples.
+struct PartitionedConvolver {
+  block: usize,
+  fft: Arc<dyn Fft<f64>>,
+  ifft: Arc<dyn Fft<f64>>,
+  partitions: Vec<Vec<Complex<f64>>>,
+  spectra: Vec<Vec<Complex<f64>>>,
+  spectrum_index: usize,
+  input: Vec<f64>,
+  output: Vec<f64>,
+  position: usize,
+  scratch: Vec<Complex<f64>>,
+}
+
+impl PartitionedConvolver {
+  fn new(block: usize, filter: &[f64], planner: &mut FftPlanner<f64>) -> Self {
+    let size = 2 * block;
+    let fft = planner.plan_fft_forward(size);
+    let ifft = planner.plan_fft_inverse(size);
+    let partitions: Vec<Vec<Complex<f64>>> = filter
+      .chunks(block)
+      .map(|chunk| {
+        let mut spectrum = vec![Complex::new(0., 0.); size];
+        for (bin, x) in spectrum.iter_mut().zip(chunk) {
+          bin.re = *x;
+        }
+        fft.process(&mut spectrum);
+        spectrum
+      })
+      .collect();
+    let spectra = vec![vec![Complex::new(0., 0.); size]; partitions.len()];
+    Self {
+      block,
+      fft,
+      ifft,
+      partitions,
+      spectra,
+      spectrum_index: 0,
+      input: vec![0.; size],
+      output: vec![0.; block],
+      position: 0,
+      scratch: vec![Complex::new(0., 0.); size],
+    }
+  }
+  fn process(&mut self, x: f64) -> f64 {
+    let y = self.output[self.position];
+    self.input[self.block + self.position] = x;
+    self.position += 1;
+    if self.position == self.block {
+      self.position = 0;
+      self.process_block();
+    }
+    y
+  }
+  fn process_block(&mut self) {
+    if self.partitions.is_empty() {
+      return;
+    }
+    let count = self.partitions.len();
+    self.spectrum_index = (self.spectrum_index + 1) % count;
+    let spectrum = &mut self.spectra[self.spectrum_index];
+    for (bin, x) in spectrum.iter_mut().zip(self.input.iter()) {
+      *bin = Complex::new(*x, 0.);
+    }
+    self.fft.process(spectrum);
+    self
+      .scratch
+      .iter_mut()
+      .for_each(|bin| *bin = Complex::new(0., 0.));
+    for (age, partition) in self.partitions.iter().enumerate() {
+      let spectrum = &self.spectra[(self.spectrum_index + count - age) % count];
+      for ((accumulator, x), h) in
+        self.scratch.iter_mut().zip(spectrum).zip(partition)
+      {
+        *accumulator += x * h;
+      }
+    }
+    self.ifft.process(&mut self.scratch);
+    let normalization = 1. / (2 * self.block) as f64;
+    for (y, bin) in self.output.iter_mut().zip(&self.scratch[self.block..]) {
+      *y = bin.re * normalization;
+    }
+    self.input.copy_within(self.block.., 0);
+  }
+}
+
+fn build_stages(
+  impulse_response: &[f64],
+  partitioning: ConvolutionPartitioning,
+) -> (Vec<PartitionedConvolver>, usize) {
+  let mut planner = FftPlanner::new();
+  match partitioning {
+    ConvolutionPartitioning::Uniform(block) => {
+      let block = block.max(1);
+      (
+        vec![PartitionedConvolver::new(
+          block,
+          impulse_response,
+          &mut planner,
+        )],
+        block,
+      )
+    }
+    ConvolutionPartitioning::NonUniform {
+      min_block,
+      max_block,
+    } => {
+      let min_block = min_block.max(1);
+      let mut stages = vec![];
+      let mut block = min_block;
+      let mut start = 0;
+      while start < impulse_response.len() {
+        let next_block = (block * 4).min(max_block.max(min_block));
+        let end = if next_block == block {
+          impulse_response.len()
+        } else {
+          (start + 4 * block)
+            .max(next_block)
+            .min(impulse_response.len())
+        };
+        // A stage with a bigger block has more latency, so its slice of the
+        // impulse response is shifted earlier by the difference to line it
+        // up with the first stage.
+        let shift = block - min_block;
+        let mut filter = vec![0.; end - shift];
+        filter[start - shift..].copy_from_slice(&impulse_response[start..end]);
+        stages.push(PartitionedConvolver::new(block, &filter, &mut planner));
+        start = end;
+        block = next_block;
+      }
+      (stages, min_block)
+    }
+  }
+}
+
+pub struct Convolution<S: Signal> {
+  child: S,
+  impulse_response: Vec<f64>,
+  impulse_response_sample_rate: f64,
+  partitioning: ConvolutionPartitioning,
+  stages: Vec<PartitionedConvolver>,
+  latency: usize,
+  // the sample rate the stages were built for, if they have been built
+  prepared_sample_rate: Option<f64>,
+  // the first input, held until a second sample reveals the sample rate
+  first_input: Option<f64>,
+  dry_line: DelayLine,
+  wet: f64,
+  clock: SampleClock,
+}
+derive_signal_ops!(Convolution<S: Signal>);
+impl<S: Signal> Convolution<S> {
+  pub fn new(
+    impulse_response: Vec<f64>,
+    impulse_response_sample_rate: f64,
+    child: S,
+  ) -> Self {
+    Self {
+      child,
+      impulse_response,
+      impulse_response_sample_rate,
+      partitioning: ConvolutionPartitioning::NonUniform {
+        min_block: 64,
+        max_block: 4096,
+      },
+      stages: vec![],
+      latency: 0,
+      prepared_sample_rate: None,
+      first_input: None,
+      dry_line: DelayLine::new(0.),
+      wet: 1.,
+      clock: SampleClock::new(),
+    }
+  }
+  pub fn from_wav<P: AsRef<Path>>(path: P, child: S) -> Result<Self> {
+    let wav = load_wav(path)?;
+    Ok(Self::new(wav.samples, wav.sample_rate, child))
+  }
+  pub fn with_partitioning(
+    mut self,
+    partitioning: ConvolutionPartitioning,
+  ) -> Self {
+    self.partitioning = partitioning;
+    if let Some(sample_rate) = self.prepared_sample_rate {
+      self.prepare(sample_rate);
+    }
+    self
+  }
+  // Builds the partitions for the given output sample rate up front, so the
+  // resampling, FFT planning and allocation stay off the audio thread.
+  // Otherwise they happen on the second sample, once the spacing between the
+  // first two samples has given the sample rate.
+  pub fn with_sample_rate(mut self, sample_rate: f64) -> Self {
+    self.prepare(sample_rate);
+    self
+  }
+  fn prepare(&mut self, sample_rate: f64) {
+    let impulse_response = resample(
+      &self.impulse_response,
+      self.impulse_response_sample_rate,
+      sample_rate,
+    );
+    (self.stages, self.latency) =
+      build_stages(&impulse_response, self.partitioning);
+    self.dry_line = DelayLine::new((self.latency + 1) as f64 / sample_rate);
+    self.prepared_sample_rate = Some(sample_rate);
+  }
+  pub fn with_mix(mut self, wet: f64) -> Self {
+    self.wet = wet;
+    self
+  }
+  fn process(&mut self, x: f64, sample_rate: f64) -> f64 {
+    let wet = self.stages.iter_mut().map(|stage| stage.process(x)).sum();
+    let dry = self
+      .dry_line
+      .read(self.latency as f64, DelayInterpolation::Linear);
+    self.dry_line.push(x, sample_rate);
+    mix(dry, wet, self.wet)
+  }
+}
+impl<S: Signal> Signal for Convolution<S> {
+  fn sample(&mut self, t: f64) -> f64 {
+    let measured_sample_rate = 1. / self.clock.tick(t);
+    let x = self.child.sample(t);
+    let sample_rate = match (self.prepared_sample_rate, self.first_input) {
+      (Some(sample_rate), _) => sample_rate,
+      (None, None) => {
+        // both paths are delayed by at least one sample, so the first output
+        // is silent either way
+        self.first_input = Some(x);
+        return 0.;
+      }
+      (None, Some(first_input)) => {
+        self.prepare(measured_sample_rate);
+        self.process(first_input, measured_sample_rate);
+        measured_sample_rate
+      }
+    };
+    self.process(x, sample_rate)
+  }
+}
+
+// Convolves a whole rendered buffer at once, for offline processing.
+pub fn convolve(signal: &[f64], impulse_response: &[f64]) -> Vec<f64> {
+  if signal.is_empty() || impulse_response.is_empty() {
+    return vec![];
+  }
+  let length = signal.len() + impulse_response.len() - 1;
+  let size = length.next_power_of_two();
+  let mut planner = FftPlanner::new();
+  let fft = planner.plan_fft_forward(size);
+  let to_spectrum = |samples: &[f64]| {
+    let mut spectrum = vec![Complex::new(0., 0.); size];
+    for (bin, x) in spectrum.iter_mut().zip(samples) {
+      bin.re = *x;
+    }
+    fft.process(&mut spectrum);
+    spectrum
+  };
+  let signal_spectrum = to_spectrum(signal);
+  let mut spectrum = to_spectrum(impulse_response);
+  for (bin, x) in spectrum.iter_mut().zip(signal_spectrum) {
+    *bin *= x;
+  }
+  planner.plan_fft_inverse(size).process(&mut spectrum);
+  spectrum[..length]
+    .iter()
+    .map(|bin| bin.re / size as f64)
+    .collect()
+}
@@ -1,3 +1,9 @@
+use std::path::Path;
+
+use anyhow::Result;
+
+use crate::util::mix;
+use crate::wav::load_wav;
 use crate::{derive_signal_ops, Signal};
 
 #[derive(Debug, Clone)]
@@ -63,6 +69,9 @@ impl<S: Signal> Table<S> {
     self.strategy = strategy;
     self
   }
+  pub fn values(&self) -> &[f64] {
+    &self.table
+  }
 }
 
 impl<S: Signal> Signal for Table<S> {
@@ -110,3 +119,50 @@ impl<S: Signal> Signal for FromUni<S> {
     (self.0.sample(t) * 2.) - 1.
   }
 }
+
+// Plays back a buffer of samples recorded at `sample_rate`, such as a loaded
+// WAV file or a rendered signal.
+#[derive(Debug, Clone)]
+pub struct Playback {
+  samples: Vec<f64>,
+  sample_rate: f64,
+  looping: bool,
+}
+derive_signal_ops!(Playback);
+impl Playback {
+  pub fn new(samples: Vec<f64>, sample_rate: f64) -> Self {
+    Self {
+      samples,
+      sample_rate,
+      looping: false,
+    }
+  }
+  pub fn from_wav<P: AsRef<Path>>(path: P) -> Result<Self> {
+    let wav = load_wav(path)?;
+    Ok(Self::new(wav.samples, wav.sample_rate))
+  }
+  pub fn looping(mut self) -> Self {
+    self.looping = true;
+    self
+  }
+}
+impl Signal for Playback {
+  fn sample(&mut self, t: f64) -> f64 {
+    let length = self.samples.len();
+    if length == 0 {
+      return 0.;
+    }
+    let mut position = t * self.sample_rate;
+    if self.looping {
+      position = position.rem_euclid(length as f64);
+    } else if position < 0. || position >= (length - 1) as f64 {
+      return 0.;
+    }
+    let i = position.floor() as usize;
+    mix(
+      self.samples[i],
+      self.samples[(i + 1) % length],
+      position - i as f64,
+    )
+  }
+}
@@ -0,0 +1,198 @@
+use std::sync::{Arc, Mutex};
+
+use crate::util::{mix, SampleClock};
+use crate::{derive_signal_ops, Const, Signal};
+
+#[derive(Debug, Clone, Copy)]
+pub enum DelayInterpolation {
+  Linear,
+  AllPass,
+  Cubic,
+}
+
+// A circular buffer sized in seconds. Since the sample rate is only known
+// once samples start arriving, the buffer is allocated on the first `push`,
+// and reallocated if the measured rate later turns out to differ noticeably
+// from the one it was sized for.
+#[derive(Debug, Clone)]
+pub struct DelayLine {
+  buffer: Vec<f64>,
+  write_index: usize,
+  max_delay: f64,
+  sample_rate: f64,
+  allpass_last: f64,
+}
+
+impl DelayLine {
+  pub fn new(max_delay: f64) -> Self {
+    Self {
+      buffer: vec![],
+      write_index: 0,
+      max_delay,
+      sample_rate: 0.,
+      allpass_last: 0.,
+    }
+  }
+  pub fn len(&self) -> usize {
+    self.buffer.len()
+  }
+  pub fn is_empty(&self) -> bool {
+    self.buffer.is_empty()
+  }
+  pub fn clear(&mut self) {
+    self.buffer.iter_mut().for_each(|x| *x = 0.);
+    self.allpass_last = 0.;
+  }
+  pub fn push(&mut self, value: f64, sample_rate: f64) {
+    if self.buffer.is_empty()
+      || (sample_rate - self.sample_rate).abs() > 0.01 * self.sample_rate
+    {
+      let length = (self.max_delay * sample_rate).ceil() as usize + 4;
+      // carry over what has been pushed so far, oldest first
+      let kept = self.buffer.len().min(length);
+      let mut buffer: Vec<f64> = (0..kept).rev().map(|i| self.tap(i)).collect();
+      buffer.resize(length, 0.);
+      self.buffer = buffer;
+      self.write_index = kept % length;
+      self.sample_rate = sample_rate;
+    }
+    self.buffer[self.write_index] = value;
+    self.write_index = (self.write_index + 1) % self.buffer.len();
+  }
+  // `offset` 0 is the most recently pushed value.
+  pub fn tap(&self, offset: usize) -> f64 {
+    let length = self.buffer.len();
+    if length == 0 {
+      return 0.;
+    }
+    self.buffer[(self.write_index + length - 1 - offset % length) % length]
+  }
+  // Reads the value pushed `delay` samples ago, so this should be called
+  // before pushing the current sample. `delay` is clamped to at least 1.
+  pub fn read(&mut self, delay: f64, interpolation: DelayInterpolation) -> f64 {
+    if self.buffer.is_empty() {
+      return 0.;
+    }
+    let offset = (delay - 1.).clamp(0., (self.buffer.len() - 4) as f64);
+    let mut i = offset.floor() as usize;
+    let mut p = offset - i as f64;
+    match interpolation {
+      DelayInterpolation::Linear => mix(self.tap(i), self.tap(i + 1), p),
+      DelayInterpolation::AllPass => {
+        // keep the fractional part in [0.5, 1.5) where the first-order
+        // all-pass approximates a fractional delay well
+        if p < 0.5 && i > 0 {
+          i -= 1;
+          p += 1.;
+        }
+        let eta = (1. - p.max(0.1)) / (1. + p.max(0.1));
+        let value =
+          eta * self.tap(i) + self.tap(i + 1) - eta * self.allpass_last;
+        self.allpass_last = value;
+        value
+      }
+      DelayInterpolation::Cubic => {
+        let y_newer = self.tap(i.saturating_sub(1));
+        let y_0 = self.tap(i);
+        let y_1 = self.tap(i + 1);
+        let y_2 = self.tap(i + 2);
+        let c1 = 0.5 * (y_1 - y_newer);
+        let c2 = y_newer - 2.5 * y_0 + 2. * y_1 - 0.5 * y_2;
+        let c3 = 0.5 * (y_2 - y_newer) + 1.5 * (y_0 - y_1);
+        ((c3 * p + c2) * p + c1) * p + y_0
+      }
+    }
+  }
+}
+
+// Stands in for the delayed signal inside a feedback path, so that existing
+// nodes like `OnePoleLowPass` or `Tanh` can process the echoes.
+#[derive(Debug, Clone)]
+pub struct DelayFeedback {
+  value: Arc<Mutex<f64>>,
+}
+derive_signal_ops!(DelayFeedback);
+impl Signal for DelayFeedback {
+  fn sample(&mut self, _t: f64) -> f64 {
+    *self.value.lock().unwrap()
+  }
+}
+
+pub struct Delay<T: Signal, S: Signal> {
+  delay_time: T,
+  child: S,
+  line: DelayLine,
+  interpolation: DelayInterpolation,
+  feedback: f64,
+  feedback_path: Option<(DelayFeedback, Box<dyn Signal>)>,
+  wet: f64,
+  clock: SampleClock,
+}
+derive_signal_ops!(Delay<T: Signal, S: Signal>);
+impl<T: Signal, S: Signal> Delay<T, S> {
+  pub fn new(max_delay: f64, delay_time: T, child: S) -> Self {
+    Self {
+      delay_time,
+      child,
+      line: DelayLine::new(max_delay),
+      interpolation: DelayInterpolation::Linear,
+      feedback: 0.,
+      feedback_path: None,
+      wet: 1.,
+      clock: SampleClock::new(),
+    }
+  }
+  pub fn with_interpolation(
+    mut self,
+    interpolation: DelayInterpolation,
+  ) -> Self {
+    self.interpolation = interpolation;
+    self
+  }
+  pub fn with_feedback(mut self, feedback: f64) -> Self {
+    self.feedback = feedback;
+    self
+  }
+  pub fn with_feedback_path<
+    P: Signal + 'static,
+    F: FnOnce(DelayFeedback) -> P,
+  >(
+    mut self,
+    f: F,
+  ) -> Self {
+    let feedback = DelayFeedback {
+      value: Arc::new(Mutex::new(0.)),
+    };
+    let path = f(feedback.clone());
+    self.feedback_path = Some((feedback, Box::new(path)));
+    self
+  }
+  pub fn with_mix(mut self, wet: f64) -> Self {
+    self.wet = wet;
+    self
+  }
+}
+impl<S: Signal> Delay<Const, S> {
+  pub fn constant(delay_time: f64, child: S) -> Self {
+    Self::new(delay_time, Const(delay_time), child)
+  }
+}
+impl<T: Signal, S: Signal> Signal for Delay<T, S> {
+  fn sample(&mut self, t: f64) -> f64 {
+    let sample_rate = 1. / self.clock.tick(t);
+    let delay = self.delay_time.sample(t) * sample_rate;
+    let input = self.child.sample(t);
+    let delayed = self.line.read(delay, self.interpolation);
+    let fed_back = match &mut self.feedback_path {
+      Some((feedback, path)) => {
+        *feedback.value.lock().unwrap() = delayed;
+        path.sample(t)
+      }
+      None => delayed,
+    };
+    self
+      .line
+      .push(input + self.feedback * fed_back, sample_rate);
+    mix(input, delayed, self.wet)
+  }
+}
@@ -0,0 +1,371 @@
+use std::collections::VecDeque;
+
+use crate::util::{
+  amplitude_to_db, db_to_amplitude, one_pole_coefficient, SampleClock,
+};
+use crate::{derive_signal_ops, DelayInterpolation, DelayLine, Signal};
+
+#[derive(Debug, Clone, Copy)]
+pub enum DetectorMode {
+  Peak,
+  Rms,
+}
+
+// Tracks the level of a signal with separate attack and release times (in
+// seconds).
+#[derive(Debug, Clone)]
+pub struct LevelDetector {
+  pub mode: DetectorMode,
+  pub attack: f64,
+  pub release: f64,
+  state: f64,
+}
+
+impl LevelDetector {
+  pub fn new(mode: DetectorMode, attack: f64, release: f64) -> Self {
+    Self {
+      mode,
+      attack,
+      release,
+      state: 0.,
+    }
+  }
+  pub fn process(&mut self, x: f64, dt: f64) -> f64 {
+    let x = match self.mode {
+      DetectorMode::Peak => x.abs(),
+      DetectorMode::Rms => x * x,
+    };
+    let time = if x > self.state {
+      self.attack
+    } else {
+      self.release
+    };
+    let a = one_pole_coefficient(time, dt);
+    self.state = a * self.state + (1. - a) * x;
+    self.level()
+  }
+  pub fn level(&self) -> f64 {
+    match self.mode {
+      DetectorMode::Peak => self.state,
+      DetectorMode::Rms => self.state.sqrt(),
+    }
+  }
+}
+
+pub struct EnvelopeFollower<S: Signal> {
+  detector: LevelDetector,
+  child: S,
+  clock: SampleClock,
+}
+derive_signal_ops!(EnvelopeFollower<S: Signal>);
+impl<S: Signal> EnvelopeFollower<S> {
+  pub fn new(attack: f64, release: f64, child: S) -> Self {
+    Self {
+      detector: LevelDetector::new(DetectorMode::Peak, attack, release),
+      child,
+      clock: SampleClock::new(),
+    }
+  }
+  pub fn with_mode(mut self, mode: DetectorMode) -> Self {
+    self.detector.mode = mode;
+    self
+  }
+}
+impl<S: Signal> Signal for EnvelopeFollower<S> {
+  fn sample(&mut self, t: f64) -> f64 {
+    let dt = self.clock.tick(t);
+    let x = self.child.sample(t);
+    self.detector.process(x, dt)
+  }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum GainCurve {
+  Compress,
+  Expand,
+}
+
+// Shared detector and gain computer. Levels and gains are in dB, times are
+// in seconds.
+struct DynamicsCore {
+  curve: GainCurve,
+  threshold: f64,
+  ratio: f64,
+  knee: f64,
+  detector: LevelDetector,
+  makeup: f64,
+  range: f64,
+  hold: f64,
+  hold_remaining: f64,
+  sidechain: Option<Box<dyn Signal>>,
+  clock: SampleClock,
+}
+
+impl DynamicsCore {
+  fn new(curve: GainCurve, threshold: f64, ratio: f64) -> Self {
+    Self {
+      curve,
+      threshold,
+      ratio: ratio.max(1.),
+      knee: 0.,
+      detector: LevelDetector::new(DetectorMode::Peak, 0.005, 0.1),
+      makeup: 0.,
+      range: f64::NEG_INFINITY,
+      hold: 0.,
+      hold_remaining: 0.,
+      sidechain: None,
+      clock: SampleClock::new(),
+    }
+  }
+  fn static_curve(&self, level_db: f64) -> f64 {
+    let (threshold, ratio, knee) = (self.threshold, self.ratio, self.knee);
+    let over = level_db - threshold;
+    match self.curve {
+      GainCurve::Compress => {
+        if 2. * over < -knee {
+          level_db
+        } else if knee > 0. && 2. * over.abs() <= knee {
+          level_db
+            + (1. / ratio - 1.) * (over + knee / 2.).powi(2) / (2. * knee)
+        } else {
+          threshold + over / ratio
+        }
+      }
+      GainCurve::Expand => {
+        if 2. * over > knee {
+          level_db
+        } else if knee > 0. && 2. * over.abs() <= knee {
+          level_db - (ratio - 1.) * (over - knee / 2.).powi(2) / (2. * knee)
+        } else {
+          threshold + over * ratio
+        }
+      }
+    }
+  }
+  // Returns the linear gain to apply to the current input sample.
+  fn gain(&mut self, t: f64, input: f64) -> f64 {
+    let dt = self.clock.tick(t);
+    let detected = match &mut self.sidechain {
+      Some(sidechain) => sidechain.sample(t),
+      None => input,
+    };
+    let level_db = amplitude_to_db(self.detector.process(detected, dt));
+    if level_db > self.threshold {
+      self.hold_remaining = self.hold;
+    } else {
+      self.hold_remaining -= dt;
+    }
+    let gain_db = if self.hold_remaining > 0. {
+      0.
+    } else {
+      (self.static_curve(level_db) - level_db).max(self.range)
+    };
+    db_to_amplitude(gain_db + self.makeup)
+  }
+}
+
+macro_rules! dynamics_builders {
+  ($t:ident) => {
+    impl<S: Signal> $t<S> {
+      pub fn with_attack(mut self, attack: f64) -> Self {
+        self.core.detector.attack = attack;
+        self
+      }
+      pub fn with_release(mut self, release: f64) -> Self {
+        self.core.detector.release = release;
+        self
+      }
+      pub fn with_detector(mut self, mode: DetectorMode) -> Self {
+        self.core.detector.mode = mode;
+        self
+      }
+      pub fn with_knee(mut self, knee: f64) -> Self {
+        self.core.knee = knee;
+        self
+      }
+      pub fn with_makeup(mut self, makeup: f64) -> Self {
+        self.core.makeup = makeup;
+        self
+      }
+      pub fn with_sidechain<C: Signal + 'static>(
+        mut self,
+        sidechain: C,
+      ) -> Self {
+        self.core.sidechain = Some(Box::new(sidechain));
+        self
+      }
+    }
+    impl<S: Signal> Signal for $t<S> {
+      fn sample(&mut self, t: f64) -> f64 {
+        let x = self.child.sample(t);
+        self.core.gain(t, x) * x
+      }
+    }
+  };
+}
+
+pub struct Compressor<S: Signal> {
+  core: DynamicsCore,
+  child: S,
+}
+derive_signal_ops!(Compressor<S: Signal>);
+dynamics_builders!(Compressor);
+impl<S: Signal> Compressor<S> {
+  pub fn new(threshold: f64, ratio: f64, child: S) -> Self {
+    Self {
+      core: DynamicsCore::new(GainCurve::Compress, threshold, ratio),
+      child,
+    }
+  }
+}
+
+pub struct Expander<S: Signal> {
+  core: DynamicsCore,
+  child: S,
+}
+derive_signal_ops!(Expander<S: Signal>);
+dynamics_builders!(Expander);
+impl<S: Signal> Expander<S> {
+  pub fn new(threshold: f64, ratio: f64, child: S) -> Self {
+    Self {
+      core: DynamicsCore::new(GainCurve::Expand, threshold, ratio),
+      child,
+    }
+  }
+  // the most attenuation the expander will apply, in dB (e.g. -40.)
+  pub fn with_range(mut self, range: f64) -> Self {
+    self.core.range = range;
+    self
+  }
+}
+
+pub struct Gate<S: Signal> {
+  core: DynamicsCore,
+  child: S,
+}
+derive_signal_ops!(Gate<S: Signal>);
+dynamics_builders!(Gate);
+impl<S: Signal> Gate<S> {
+  pub fn new(threshold: f64, child: S) -> Self {
+    let mut core = DynamicsCore::new(GainCurve::Expand, threshold, 100.);
+    core.detector.attack = 0.0005;
+    core.detector.release = 0.05;
+    core.range = -80.;
+    Self { core, child }
+  }
+  pub fn with_ratio(mut self, ratio: f64) -> Self {
+    self.core.ratio = ratio.max(1.);
+    self
+  }
+  pub fn with_range(mut self, range: f64) -> Self {
+    self.core.range = range;
+    self
+  }
+  // keeps the gate open for `hold` seconds after the level drops below the
+  // threshold
+  pub fn with_hold(mut self, hold: f64) -> Self {
+    self.core.hold = hold;
+    self
+  }
+}
+
+// Brickwall limiter. The input is delayed by the lookahead time so that the
+// gain can already be down by the time a peak arrives.
+pub struct Limiter<S: Signal> {
+  ceiling: f64,
+  lookahead: f64,
+  release: f64,
+  makeup: f64,
+  sidechain: Option<Box<dyn Signal>>,
+  child: S,
+  line: DelayLine,
+  peaks: VecDeque<(usize, f64)>,
+  sample_index: usize,
+  gain: f64,
+  clock: SampleClock,
+}
+derive_signal_ops!(Limiter<S: Signal>);
+impl<S: Signal> Limiter<S> {
+  pub fn new(ceiling: f64, child: S) -> Self {
+    Self {
+      ceiling,
+      lookahead: 0.005,
+      release: 0.1,
+      makeup: 0.,
+      sidechain: None,
+      child,
+      line: DelayLine::new(0.005),
+      peaks: VecDeque::new(),
+      sample_index: 0,
+      gain: 1.,
+      clock: SampleClock::new(),
+    }
+  }
+  pub fn with_lookahead(mut self, lookahead: f64) -> Self {
+    self.lookahead = lookahead;
+    self.line = DelayLine::new(lookahead);
+    self
+  }
+  pub fn with_release(mut self, release: f64) -> Self {
+    self.release = release;
+    self
+  }
+  pub fn with_makeup(mut self, makeup: f64) -> Self {
+    self.makeup = makeup;
+    self
+  }
+  pub fn with_sidechain<C: Signal + 'static>(mut self, sidechain: C) -> Self {
+    self.sidechain = Some(Box::new(sidechain));
+    self
+  }
+}
+impl<S: Signal> Signal for Limiter<S> {
+  fn sample(&mut self, t: f64) -> f64 {
+    let dt = self.clock.tick(t);
+    let sample_rate = 1. / dt;
+    let lookahead_samples = (self.lookahead * sample_rate).round().max(1.);
+    let makeup = db_to_amplitude(self.makeup);
+    let x = makeup * self.child.sample(t);
+    let detected = match &mut self.sidechain {
+      Some(sidechain) => makeup * sidechain.sample(t),
+      None => x,
+    }
+    .abs();
+
+    // sliding window maximum of the detected level over the lookahead time
+    self.sample_index += 1;
+    while self
+      .peaks
+      .back()
+      .map(|(_, peak)| *peak <= detected)
+      .unwrap_or(false)
+    {
+      self.peaks.pop_back();
+    }
+    self.peaks.push_back((self.sample_index, detected));
+    while self
+      .peaks
+      .front()
+      .map(|(index, _)| (self.sample_index - index) as f64 > lookahead_samples)
+      .unwrap_or(false)
+    {
+      self.peaks.pop_front();
+    }
+    let peak = self.peaks.front().map(|(_, peak)| *peak).unwrap_or(0.);
+
+    let ceiling = db_to_amplitude(self.ceiling);
+    let target = if peak > ceiling { ceiling / peak } else { 1. };
+    let time = if target < self.gain {
+      self.lookahead / 5.
+    } else {
+      self.release
+    };
+    let a = one_pole_coefficient(time, dt);
+    self.gain = a * self.gain + (1. - a) * target;
+    let delayed = self
+      .line
+      .read(lookahead_samples, DelayInterpolation::Linear);
+    self.line.push(x, sample_rate);
+    (self.gain * delayed).clamp(-ceiling, ceiling)
+  }
+}
@@ -1,12 +1,84 @@
-use crate::util::mix;
+use crate::util::{mix, SampleClock};
 use crate::{derive_signal_ops, Const, Signal};
 
+// Each stage holds its start time and the level it started from, so that
+// a stage can begin wherever the previous one left off.
 pub enum ADSRState {
   Off,
-  Attack(f64),
-  Decay(f64),
+  Attack(f64, f64),
+  Decay(f64, f64),
   Sustain,
-  Release(f64),
+  Release(f64, f64),
+}
+
+// The shape of an envelope segment. Exponential and logarithmic curves are
+// mirrored for falling segments, so an exponential decay drops quickly and
+// then tails off, while an exponential attack starts slowly. `Power` raises
+// the segment's progress to the given exponent regardless of direction.
+// `Analog` mimics a capacitor charging toward a target beyond the peak for
+// attacks and discharging for decays and releases.
+#[derive(Debug, Clone, Copy)]
+pub enum EnvelopeCurve {
+  Linear,
+  Exponential,
+  Logarithmic,
+  Power(f64),
+  Analog,
+}
+
+fn exponential_shape(p: f64, k: f64) -> f64 {
+  ((k * p).exp() - 1.) / (k.exp() - 1.)
+}
+
+fn rc_shape(p: f64, k: f64) -> f64 {
+  (1. - (-k * p).exp()) / (1. - (-k).exp())
+}
+
+impl EnvelopeCurve {
+  pub fn shape(&self, p: f64, rising: bool) -> f64 {
+    let p = p.clamp(0., 1.);
+    match *self {
+      EnvelopeCurve::Linear => p,
+      EnvelopeCurve::Exponential => {
+        if rising {
+          exponential_shape(p, 5.)
+        } else {
+          1. - exponential_shape(1. - p, 5.)
+        }
+      }
+      EnvelopeCurve::Logarithmic => {
+        if rising {
+          1. - exponential_shape(1. - p, 5.)
+        } else {
+          exponential_shape(p, 5.)
+        }
+      }
+      EnvelopeCurve::Power(curvature) => p.powf(curvature.max(1e-3)),
+      EnvelopeCurve::Analog => {
+        if rising {
+          rc_shape(p, 1.5)
+        } else {
+          rc_shape(p, 5.)
+        }
+      }
+    }
+  }
+  // interpolates from `from` to `to` at progress `p` along the curve
+  pub fn segment(&self, from: f64, to: f64, p: f64) -> f64 {
+    mix(from, to, self.shape(p, to > from))
+  }
+}
+
+// What happens when the gate opens again while the envelope is still
+// releasing.
+#[derive(Debug, Clone, Copy)]
+pub enum RetriggerMode {
+  // restart the attack from the current level
+  FromCurrent,
+  // restart the attack from zero
+  FromZero,
+  // skip the attack and head back to the sustain level
+  Legato,
 }
 
 pub struct ADSR<A: Signal, D: Signal, S: Signal, R: Signal, G: Signal> {
@@ -16,6 +88,19 @@ pub struct ADSR<A: Signal, D: Signal, S: Signal, R: Signal, G: Signal> {
   release: R,
   state: ADSRState,
   gate: G,
+  gate_on: bool,
+  value: f64,
+  retrigger: RetriggerMode,
+  attack_curve: EnvelopeCurve,
+  decay_curve: EnvelopeCurve,
+  release_curve: EnvelopeCurve,
+  velocity: Option<Box<dyn Signal>>,
+  velocity_to_level: f64,
+  velocity_to_attack: f64,
+  key: Option<Box<dyn Signal>>,
+  key_tracking: f64,
+  peak: f64,
+  attack_scale: f64,
 }
 derive_signal_ops!(ADSR<A:Signal, D:Signal, S:Signal, R:Signal, G: Signal>);
 use ADSRState::*;
@@ -31,8 +116,65 @@ impl<A: Signal, D: Signal, S: Signal, R: Signal, G: Signal>
       release,
       state: ADSRState::Off,
       gate,
+      gate_on: false,
+      value: 0.,
+      retrigger: RetriggerMode::FromCurrent,
+      attack_curve: EnvelopeCurve::Linear,
+      decay_curve: EnvelopeCurve::Linear,
+      release_curve: EnvelopeCurve::Linear,
+      velocity: None,
+      velocity_to_level: 0.,
+      velocity_to_attack: 0.,
+      key: None,
+      key_tracking: 0.,
+      peak: 1.,
+      attack_scale: 1.,
     }
   }
+  pub fn with_retrigger(mut self, retrigger: RetriggerMode) -> Self {
+    self.retrigger = retrigger;
+    self
+  }
+  pub fn with_curve(self, curve: EnvelopeCurve) -> Self {
+    self.with_curves(curve, curve, curve)
+  }
+  pub fn with_curves(
+    mut self,
+    attack_curve: EnvelopeCurve,
+    decay_curve: EnvelopeCurve,
+    release_curve: EnvelopeCurve,
+  ) -> Self {
+    self.attack_curve = attack_curve;
+    self.decay_curve = decay_curve;
+    self.release_curve = release_curve;
+    self
+  }
+  // Scales the envelope by a velocity signal from 0 to 1, such as a
+  // `MidiVelocity`, read each time the gate opens. At a `level_amount` of 1
+  // the peak equals the velocity, and at an `attack_amount` of 1 a full
+  // velocity note has no attack.
+  pub fn with_velocity<V: Signal + 'static>(
+    mut self,
+    velocity: V,
+    level_amount: f64,
+    attack_amount: f64,
+  ) -> Self {
+    self.velocity = Some(Box::new(velocity));
+    self.velocity_to_level = level_amount;
+    self.velocity_to_attack = attack_amount;
+    self
+  }
+  // Shortens decay and release for higher keys. `key` is a MIDI note number,
+  // and at an `amount` of 1 the times halve with each octave above middle C.
+  pub fn with_key_tracking<K: Signal + 'static>(
+    mut self,
+    key: K,
+    amount: f64,
+  ) -> Self {
+    self.key = Some(Box::new(key));
+    self.key_tracking = amount;
+    self
+  }
 }
 
 impl<G: Signal> ADSR<Const, Const, Const, Const, G> {
@@ -53,180 +195,469 @@ impl<G: Signal> ADSR<Const, Const, Const, Const, G> {
   }
 }
 
+fn stage_progress(elapsed: f64, duration: f64) -> f64 {
+  if duration > 0. {
+    (elapsed / duration).clamp(0., 1.)
+  } else {
+    1.
+  }
+}
+
 impl<A: Signal, D: Signal, S: Signal, R: Signal, G: Signal> Signal
   for ADSR<A, D, S, R, G>
 {
   fn sample(&mut self, t: f64) -> f64 {
     let on = self.gate.sample(t) > 0.5;
+    let rising = on && !self.gate_on;
+    self.gate_on = on;
+    if rising {
+      if let Some(velocity) = &mut self.velocity {
+        let velocity = velocity.sample(t).clamp(0., 1.);
+        self.peak = mix(1., velocity, self.velocity_to_level);
+        self.attack_scale = mix(1., 1. - velocity, self.velocity_to_attack);
+      }
+    }
+    let key_scale = match &mut self.key {
+      Some(key) => (-self.key_tracking * (key.sample(t) - 60.) / 12.).exp2(),
+      None => 1.,
+    };
+    let peak = self.peak;
+    let attack = self.attack.sample(t) * self.attack_scale;
+    let decay = self.decay.sample(t) * key_scale;
+    let sustain = self.sustain.sample(t) * peak;
+    let release = self.release.sample(t) * key_scale;
+    let level = self.value;
+    // an attack from a nonzero level covers less distance, so it takes
+    // proportionally less time
+    let attack_duration = |from: f64| {
+      if peak > 0. {
+        attack * (1. - from / peak).max(0.)
+      } else {
+        0.
+      }
+    };
     let new_state = match self.state {
       Off => {
         if on {
-          Attack(t)
+          Attack(t, 0.)
         } else {
           Off
         }
       }
-      Attack(start_t) => {
-        let decay_start = start_t + self.attack.sample(t);
-        if t >= decay_start {
-          Decay(decay_start)
+      Attack(start_t, from) => {
+        let decay_start = start_t + attack_duration(from);
+        if !on {
+          Release(t, level)
+        } else if from >= peak {
+          Decay(start_t, from)
+        } else if t >= decay_start {
+          Decay(decay_start, peak)
         } else {
-          Attack(start_t)
+          Attack(start_t, from)
         }
       }
-      Decay(start_t) => {
-        let sustain_start = start_t + self.decay.sample(t);
-        if t >= sustain_start {
-          if on {
-            Sustain
-          } else {
-            Release(sustain_start)
-          }
+      Decay(start_t, from) => {
+        let sustain_start = start_t + decay;
+        if !on {
+          Release(t, level)
+        } else if t >= sustain_start {
+          Sustain
         } else {
-          Decay(start_t)
+          Decay(start_t, from)
         }
       }
       Sustain => {
         if on {
           Sustain
         } else {
-          Release(t)
+          Release(t, level)
         }
       }
-      Release(start_t) => {
-        let end_t = start_t + self.release.sample(t);
-        if t >= end_t {
+      Release(start_t, from) => {
+        if rising {
+          match self.retrigger {
+            // a softer note can retrigger above its own peak, in which case
+            // it heads straight down from the current level
+            RetriggerMode::FromCurrent if level >= peak => Decay(t, level),
+            RetriggerMode::FromCurrent => Attack(t, level),
+            RetriggerMode::FromZero => Attack(t, 0.),
+            RetriggerMode::Legato => Decay(t, level),
+          }
+        } else if t >= start_t + release {
           Off
         } else {
-          Release(start_t)
+          Release(start_t, from)
         }
       }
     };
     self.state = new_state;
-    match self.state {
+    self.value = match self.state {
       Off => 0.0,
-      Attack(start_t) => (t - start_t) / self.attack.sample(t),
-      Decay(start_t) => mix(
-        1.,
-        self.sustain.sample(t),
-        (t - start_t) / self.decay.sample(t),
+      Attack(start_t, from) => self.attack_curve.segment(
+        from,
+        peak,
+        stage_progress(t - start_t, attack_duration(from)),
       ),
-      Sustain => self.sustain.sample(t),
-      Release(start_t) => {
-        self.sustain.sample(t) * (1.0 - (t - start_t) / self.release.sample(t))
-      }
-    }
+      Decay(start_t, from) => self.decay_curve.segment(
+        from,
+        sustain,
+        stage_progress(t - start_t, decay),
+      ),
+      Sustain => sustain,
+      Release(start_t, from) => self.release_curve.segment(
+        from,
+        0.,
+        stage_progress(t - start_t, release),
+      ),
+    };
+    self.value
   }
 }
 
-pub struct ExpImpulse<S: Signal> {
-  gate: S,
-  active: bool,
-  start_t: f64,
+enum MSEGState {
+  Idle,
+  Segment(usize),
+  Sustain(usize),
 }
-derive_signal_ops!(ExpImpulse<S: Signal>);
-impl<S: Signal> ExpImpulse<S> {
-  pub fn new(gate: S) -> Self {
+
+// A multi-segment envelope built from (time, level, curve) breakpoints.
+// Times are measured from the gate opening and should be nondecreasing; each
+// curve shapes the segment leading into its breakpoint. The first segment
+// starts from whatever level the envelope was at when the gate opened.
+pub struct MSEG<G: Signal> {
+  breakpoints: Vec<(f64, f64, EnvelopeCurve)>,
+  gate: G,
+  gate_on: bool,
+  sustain: Option<usize>,
+  loop_points: Option<(usize, usize)>,
+  beat_duration: Option<Box<dyn Signal>>,
+  clock: SampleClock,
+  state: MSEGState,
+  position: f64,
+  segment_start: f64,
+  from: f64,
+  value: f64,
+}
+derive_signal_ops!(MSEG<G: Signal>);
+
+impl<G: Signal> MSEG<G> {
+  pub fn new(breakpoints: Vec<(f64, f64, EnvelopeCurve)>, gate: G) -> Self {
     Self {
+      breakpoints,
       gate,
-      active: false,
-      start_t: -f64::MAX,
+      gate_on: false,
+      sustain: None,
+      loop_points: None,
+      beat_duration: None,
+      clock: SampleClock::new(),
+      state: MSEGState::Idle,
+      position: 0.,
+      segment_start: 0.,
+      from: 0.,
+      value: 0.,
     }
   }
+  // holds at the breakpoint with this index while the gate is open
+  pub fn with_sustain(mut self, index: usize) -> Self {
+    self.sustain = Some(index);
+    self
+  }
+  // cycles from the `end` breakpoint back to the `start` breakpoint while the
+  // gate is open
+  pub fn with_loop(mut self, start: usize, end: usize) -> Self {
+    self.loop_points = Some((start, end));
+    self
+  }
+  // measures breakpoint times in beats of the given length in seconds, e.g.
+  // the spacing of a `GateBeat` clock
+  pub fn with_tempo_sync<B: Signal + 'static>(
+    mut self,
+    beat_duration: B,
+  ) -> Self {
+    self.beat_duration = Some(Box::new(beat_duration));
+    self
+  }
+  fn start_segment(&mut self, index: usize, from: f64) {
+    self.segment_start = if index == 0 {
+      0.
+    } else {
+      self.breakpoints[index - 1].0
+    };
+    self.position = self.position.max(self.segment_start);
+    self.from = from;
+    self.state = if index < self.breakpoints.len() {
+      MSEGState::Segment(index)
+    } else {
+      MSEGState::Idle
+    };
+  }
+  fn active_loop(&self) -> Option<(usize, usize)> {
+    self.loop_points.filter(|&(start, end)| {
+      end < self.breakpoints.len()
+        && start < end
+        && self.breakpoints[end].0 > self.breakpoints[start].0
+    })
+  }
 }
-impl<S: Signal> Signal for ExpImpulse<S> {
+
+impl<G: Signal> Signal for MSEG<G> {
   fn sample(&mut self, t: f64) -> f64 {
-    if self.gate.sample(t) > 0.5 {
-      if !self.active {
-        self.active = true;
-        self.start_t = t;
-      }
+    let dt = self.clock.tick(t);
+    let on = self.gate.sample(t) > 0.5;
+    let rising = on && !self.gate_on;
+    let falling = !on && self.gate_on;
+    self.gate_on = on;
+    let speed = match &mut self.beat_duration {
+      Some(beat_duration) => 1. / beat_duration.sample(t).max(1e-6),
+      None => 1.,
+    };
+    if rising {
+      self.position = 0.;
+      self.start_segment(0, self.value);
     } else {
-      if self.active {
-        self.active = false;
+      if falling {
+        if let Some(sustain) = self.sustain {
+          let before_release = match self.state {
+            MSEGState::Segment(index) => index <= sustain,
+            MSEGState::Sustain(_) => true,
+            MSEGState::Idle => false,
+          };
+          if before_release && sustain < self.breakpoints.len() {
+            self.position = self.breakpoints[sustain].0;
+            self.start_segment(sustain + 1, self.value);
+          }
+        }
+      }
+      if let MSEGState::Segment(_) = self.state {
+        self.position += dt * speed;
+      }
+    }
+    while let MSEGState::Segment(index) = self.state {
+      let (time, level, _) = self.breakpoints[index];
+      if self.position < time {
+        break;
+      }
+      match self.active_loop() {
+        Some((start, end)) if on && index == end => {
+          let (loop_time, loop_level, _) = self.breakpoints[start];
+          self.position = loop_time + (self.position - time);
+          self.start_segment(start + 1, loop_level);
+        }
+        _ => {
+          if on && self.sustain == Some(index) {
+            self.state = MSEGState::Sustain(index);
+          } else {
+            self.start_segment(index + 1, level);
+          }
+          self.value = level;
+        }
       }
     }
-    let adjusted_t = t - self.start_t;
-    adjusted_t * (1. - adjusted_t).exp()
+    self.value = match self.state {
+      MSEGState::Idle => self.value,
+      MSEGState::Sustain(index) => self.breakpoints[index].1,
+      MSEGState::Segment(index) => {
+        let (time, level, curve) = self.breakpoints[index];
+        curve.segment(
+          self.from,
+          level,
+          stage_progress(
+            self.position - self.segment_start,
+            time - self.segment_start,
+          ),
+        )
+      }
+    };
+    self.value
+  }
+}
+
+fn exp_impulse_shape(u: f64) -> f64 {
+  u * (1. - u).exp()
+}
+
+// Finds the point on the rising half of the impulse that has the given
+// level, so a retrigger can pick up from wherever the impulse currently is.
+fn exp_impulse_phase(level: f64) -> f64 {
+  let level = level.clamp(0., 1.);
+  let (mut low, mut high) = (0., 1.);
+  for _ in 0..32 {
+    let middle = 0.5 * (low + high);
+    if exp_impulse_shape(middle) < level {
+      low = middle;
+    } else {
+      high = middle;
+    }
   }
+  0.5 * (low + high)
 }
 
-pub struct ExpDecay<S: Signal> {
+// Rises and falls as `x * e^(1 - x)`, reaching its peak of 1 `peak_time`
+// seconds after the gate opens. Outputs 0 until it is first triggered.
+pub struct ExpImpulse<P: Signal, S: Signal> {
   gate: S,
-  active: bool,
-  start_t: f64,
+  peak_time: P,
+  gate_on: bool,
+  // trigger time and the phase the impulse restarted from
+  start: Option<(f64, f64)>,
+  value: f64,
 }
-derive_signal_ops!(ExpDecay<S: Signal>);
-impl<S: Signal> ExpDecay<S> {
-  pub fn new(gate: S) -> Self {
+derive_signal_ops!(ExpImpulse<P: Signal, S: Signal>);
+impl<P: Signal, S: Signal> ExpImpulse<P, S> {
+  pub fn new(peak_time: P, gate: S) -> Self {
     Self {
       gate,
-      active: false,
-      start_t: -f64::MAX,
+      peak_time,
+      gate_on: false,
+      start: None,
+      value: 0.,
     }
   }
 }
-impl<S: Signal> Signal for ExpDecay<S> {
+impl<S: Signal> ExpImpulse<Const, S> {
+  pub fn constant(peak_time: f64, gate: S) -> Self {
+    Self::new(Const(peak_time), gate)
+  }
+}
+impl<P: Signal, S: Signal> Signal for ExpImpulse<P, S> {
   fn sample(&mut self, t: f64) -> f64 {
-    if self.gate.sample(t) > 0.5 {
-      if !self.active {
-        self.active = true;
-        self.start_t = t;
-        println!("activating!!");
-      }
-    } else {
-      if self.active {
-        println!("deactivating!!");
-        self.active = false;
+    let on = self.gate.sample(t) > 0.5;
+    if on && !self.gate_on {
+      self.start = Some((t, exp_impulse_phase(self.value)));
+    }
+    self.gate_on = on;
+    let peak_time = self.peak_time.sample(t);
+    self.value = match self.start {
+      None => 0.,
+      Some((start_t, phase)) => {
+        let u = if peak_time > 0. {
+          phase + (t - start_t) / peak_time
+        } else {
+          f64::INFINITY
+        };
+        if u.is_finite() {
+          exp_impulse_shape(u)
+        } else {
+          0.
+        }
       }
+    };
+    self.value
+  }
+}
+
+// How long `ExpDecay` takes to rise from 0 to 1 when triggered, so that
+// triggers and retriggers ramp up from the current level instead of jumping.
+const EXP_DECAY_RAMP: f64 = 0.002;
+
+// Rises to 1 within a couple of milliseconds of the gate opening and decays
+// exponentially, falling by a factor of e every `decay` seconds. Outputs 0
+// until it is first triggered.
+pub struct ExpDecay<D: Signal, S: Signal> {
+  gate: S,
+  decay: D,
+  gate_on: bool,
+  // trigger time and the level the ramp started from
+  start: Option<(f64, f64)>,
+  value: f64,
+}
+derive_signal_ops!(ExpDecay<D: Signal, S: Signal>);
+impl<D: Signal, S: Signal> ExpDecay<D, S> {
+  pub fn new(decay: D, gate: S) -> Self {
+    Self {
+      gate,
+      decay,
+      gate_on: false,
+      start: None,
+      value: 0.,
+    }
+  }
+}
+impl<S: Signal> ExpDecay<Const, S> {
+  pub fn constant(decay: f64, gate: S) -> Self {
+    Self::new(Const(decay), gate)
+  }
+}
+fn exp_decay(elapsed: f64, decay: f64) -> f64 {
+  if decay > 0. {
+    (-elapsed.max(0.) / decay).exp()
+  } else {
+    0.
+  }
+}
+// Ramps linearly from `from` up to 1, taking `attack * (1 - from)` seconds,
+// then decays exponentially.
+fn attack_exp_decay(elapsed: f64, from: f64, attack: f64, decay: f64) -> f64 {
+  let attack_duration = attack.max(0.) * (1. - from);
+  if elapsed < attack_duration {
+    mix(from, 1., elapsed / attack_duration)
+  } else {
+    exp_decay(elapsed - attack_duration, decay)
+  }
+}
+impl<D: Signal, S: Signal> Signal for ExpDecay<D, S> {
+  fn sample(&mut self, t: f64) -> f64 {
+    let on = self.gate.sample(t) > 0.5;
+    if on && !self.gate_on {
+      self.start = Some((t, self.value.clamp(0., 1.)));
     }
-    let adjusted_t = t - self.start_t;
-    1. / adjusted_t.exp()
+    self.gate_on = on;
+    let decay = self.decay.sample(t);
+    self.value = match self.start {
+      None => 0.,
+      Some((start_t, from)) => {
+        attack_exp_decay(t - start_t, from, EXP_DECAY_RAMP, decay)
+      }
+    };
+    self.value
   }
 }
 
+// Ramps linearly up to 1 over `attack` seconds when the gate opens, then
+// decays exponentially like `ExpDecay`. A retrigger ramps up from the current
+// level, taking proportionally less time.
 pub struct AttackExpDecay<A: Signal, D: Signal, S: Signal> {
   gate: S,
-  active: bool,
-  start_t: f64,
   attack: A,
-  decay_factor: D,
+  decay: D,
+  gate_on: bool,
+  // trigger time and the level the attack started from
+  start: Option<(f64, f64)>,
+  value: f64,
 }
 derive_signal_ops!(AttackExpDecay<A:Signal, D:Signal, S: Signal>);
 impl<A: Signal, D: Signal, S: Signal> AttackExpDecay<A, D, S> {
-  pub fn new(attack: A, decay_factor: D, gate: S) -> Self {
+  pub fn new(attack: A, decay: D, gate: S) -> Self {
     Self {
       gate,
       attack,
-      decay_factor,
-      active: false,
-      start_t: -f64::MAX,
+      decay,
+      gate_on: false,
+      start: None,
+      value: 0.,
     }
   }
 }
 impl<S: Signal> AttackExpDecay<Const, Const, S> {
-  pub fn constant(attack: f64, decay_factor: f64, gate: S) -> Self {
-    Self::new(Const(attack), Const(decay_factor), gate)
+  pub fn constant(attack: f64, decay: f64, gate: S) -> Self {
+    Self::new(Const(attack), Const(decay), gate)
   }
 }
 impl<A: Signal, D: Signal, S: Signal> Signal for AttackExpDecay<A, D, S> {
   fn sample(&mut self, t: f64) -> f64 {
-    if self.gate.sample(t) > 0.5 {
-      if !self.active {
-        self.active = true;
-        self.start_t = t;
-      }
-    } else {
-      if self.active {
-        self.active = false;
-      }
+    let on = self.gate.sample(t) > 0.5;
+    if on && !self.gate_on {
+      self.start = Some((t, self.value.clamp(0., 1.)));
     }
-    let adjusted_t = t - self.start_t;
+    self.gate_on = on;
     let attack = self.attack.sample(t);
-    if adjusted_t < attack {
-      adjusted_t / attack
-    } else {
-      1. / (self.decay_factor.sample(t) * adjusted_t).exp()
-    }
+    let decay = self.decay.sample(t);
+    self.value = match self.start {
+      None => 0.,
+      Some((start_t, from)) => {
+        attack_exp_decay(t - start_t, from, attack, decay)
+      }
+    };
+    self.value
   }
 }
@@ -0,0 +1,136 @@
+use crate::util::SampleClock;
+use crate::{
+  derive_signal_ops, BiquadCoefficients, BiquadState, Const, Signal,
+};
+
+#[derive(Debug, Clone, Copy)]
+pub enum EqBandKind {
+  LowShelf,
+  Peak,
+  HighShelf,
+}
+
+pub struct EqBand {
+  kind: EqBandKind,
+  frequency: Box<dyn Signal>,
+  gain_db: Box<dyn Signal>,
+  q: Box<dyn Signal>,
+  state: BiquadState,
+}
+impl EqBand {
+  pub fn new<F: Signal + 'static, G: Signal + 'static, Q: Signal + 'static>(
+    kind: EqBandKind,
+    frequency: F,
+    gain_db: G,
+    q: Q,
+  ) -> Self {
+    Self {
+      kind,
+      frequency: Box::new(frequency),
+      gain_db: Box::new(gain_db),
+      q: Box::new(q),
+      state: BiquadState::new(),
+    }
+  }
+  pub fn constant(
+    kind: EqBandKind,
+    frequency: f64,
+    gain_db: f64,
+    q: f64,
+  ) -> Self {
+    Self::new(kind, Const(frequency), Const(gain_db), Const(q))
+  }
+  pub fn low_shelf<F: Signal + 'static, G: Signal + 'static>(
+    frequency: F,
+    gain_db: G,
+  ) -> Self {
+    Self::new(
+      EqBandKind::LowShelf,
+      frequency,
+      gain_db,
+      Const(std::f64::consts::FRAC_1_SQRT_2),
+    )
+  }
+  pub fn peak<F: Signal + 'static, G: Signal + 'static, Q: Signal + 'static>(
+    frequency: F,
+    gain_db: G,
+    q: Q,
+  ) -> Self {
+    Self::new(EqBandKind::Peak, frequency, gain_db, q)
+  }
+  pub fn high_shelf<F: Signal + 'static, G: Signal + 'static>(
+    frequency: F,
+    gain_db: G,
+  ) -> Self {
+    Self::new(
+      EqBandKind::HighShelf,
+      frequency,
+      gain_db,
+      Const(std::f64::consts::FRAC_1_SQRT_2),
+    )
+  }
+  fn coefficients(&mut self, t: f64, sample_rate: f64) -> BiquadCoefficients {
+    let frequency = self.frequency.sample(t);
+    let gain_db = self.gain_db.sample(t);
+    let q = self.q.sample(t).max(0.01);
+    match self.kind {
+      EqBandKind::LowShelf => {
+        BiquadCoefficients::low_shelf(frequency, q, gain_db, sample_rate)
+      }
+      EqBandKind::Peak => {
+        BiquadCoefficients::peaking(frequency, q, gain_db, sample_rate)
+      }
+      EqBandKind::HighShelf => {
+        BiquadCoefficients::high_shelf(frequency, q, gain_db, sample_rate)
+      }
+    }
+  }
+}
+
+pub struct Equalizer<S: Signal> {
+  bands: Vec<EqBand>,
+  clock: SampleClock,
+  child: S,
+}
+derive_signal_ops!(Equalizer<S: Signal>);
+impl<S: Signal> Equalizer<S> {
+  pub fn new(bands: Vec<EqBand>, child: S) -> Self {
+    Self {
+      bands,
+      clock: SampleClock::new(),
+      child,
+    }
+  }
+  pub fn with_band(mut self, band: EqBand) -> Self {
+    self.bands.push(band);
+    self
+  }
+  // Combined linear gain of all bands at `frequency`, with each band's
+  // parameters evaluated at time `t`.
+  pub fn magnitude_response(
+    &mut self,
+    t: f64,
+    frequency: f64,
+    sample_rate: f64,
+  ) -> f64 {
+    self
+      .bands
+      .iter_mut()
+      .map(|band| {
+        band
+          .coefficients(t, sample_rate)
+          .magnitude(frequency, sample_rate)
+      })
+      .product()
+  }
+}
+impl<S: Signal> Signal for Equalizer<S> {
+  fn sample(&mut self, t: f64) -> f64 {
+    let sample_rate = 1. / self.clock.tick(t);
+    let x = self.child.sample(t);
+    self.bands.iter_mut().fold(x, |x, band| {
+      let coefficients = band.coefficients(t, sample_rate);
+      band.state.process(&coefficients, x)
+    })
+  }
+}
@@ -24,3 +24,176 @@ impl<A: Signal, S: Signal> Signal for OnePoleLowPass<A, S> {
     value
   }
 }
+
+const TAU: f64 = 2.0 * std::f64::consts::PI;
+
+// Coefficients follow the RBJ audio EQ cookbook, normalized so that a0 = 1.
+#[derive(Debug, Clone, Copy)]
+pub struct BiquadCoefficients {
+  pub b0: f64,
+  pub b1: f64,
+  pub b2: f64,
+  pub a1: f64,
+  pub a2: f64,
+}
+
+impl BiquadCoefficients {
+  fn normalized(b0: f64, b1: f64, b2: f64, a0: f64, a1: f64, a2: f64) -> Self {
+    Self {
+      b0: b0 / a0,
+      b1: b1 / a0,
+      b2: b2 / a0,
+      a1: a1 / a0,
+      a2: a2 / a0,
+    }
+  }
+  fn omega(frequency: f64, sample_rate: f64) -> (f64, f64) {
+    let w0 = TAU * frequency.max(1.).min(0.49 * sample_rate) / sample_rate;
+    (w0.cos(), w0.sin())
+  }
+  pub fn identity() -> Self {
+    Self {
+      b0: 1.,
+      b1: 0.,
+      b2: 0.,
+      a1: 0.,
+      a2: 0.,
+    }
+  }
+  pub fn low_pass(frequency: f64, q: f64, sample_rate: f64) -> Self {
+    let (cos, sin) = Self::omega(frequency, sample_rate);
+    let alpha = sin / (2. * q);
+    Self::normalized(
+      (1. - cos) / 2.,
+      1. - cos,
+      (1. - cos) / 2.,
+      1. + alpha,
+      -2. * cos,
+      1. - alpha,
+    )
+  }
+  pub fn high_pass(frequency: f64, q: f64, sample_rate: f64) -> Self {
+    let (cos, sin) = Self::omega(frequency, sample_rate);
+    let alpha = sin / (2. * q);
+    Self::normalized(
+      (1. + cos) / 2.,
+      -(1. + cos),
+      (1. + cos) / 2.,
+      1. + alpha,
+      -2. * cos,
+      1. - alpha,
+    )
+  }
+  pub fn band_pass(frequency: f64, q: f64, sample_rate: f64) -> Self {
+    let (cos, sin) = Self::omega(frequency, sample_rate);
+    let alpha = sin / (2. * q);
+    Self::normalized(alpha, 0., -alpha, 1. + alpha, -2. * cos, 1. - alpha)
+  }
+  pub fn notch(frequency: f64, q: f64, sample_rate: f64) -> Self {
+    let (cos, sin) = Self::omega(frequency, sample_rate);
+    let alpha = sin / (2. * q);
+    Self::normalized(1., -2. * cos, 1., 1. + alpha, -2. * cos, 1. - alpha)
+  }
+  pub fn all_pass(frequency: f64, q: f64, sample_rate: f64) -> Self {
+    let (cos, sin) = Self::omega(frequency, sample_rate);
+    let alpha = sin / (2. * q);
+    Self::normalized(
+      1. - alpha,
+      -2. * cos,
+      1. + alpha,
+      1. + alpha,
+      -2. * cos,
+      1. - alpha,
+    )
+  }
+  pub fn peaking(
+    frequency: f64,
+    q: f64,
+    gain_db: f64,
+    sample_rate: f64,
+  ) -> Self {
+    let (cos, sin) = Self::omega(frequency, sample_rate);
+    let alpha = sin / (2. * q);
+    let a = 10f64.powf(gain_db / 40.);
+    Self::normalized(
+      1. + alpha * a,
+      -2. * cos,
+      1. - alpha * a,
+      1. + alpha / a,
+      -2. * cos,
+      1. - alpha / a,
+    )
+  }
+  pub fn low_shelf(
+    frequency: f64,
+    q: f64,
+    gain_db: f64,
+    sample_rate: f64,
+  ) -> Self {
+    let (cos, sin) = Self::omega(frequency, sample_rate);
+    let alpha = sin / (2. * q);
+    let a = 10f64.powf(gain_db / 40.);
+    let k = 2. * a.sqrt() * alpha;
+    Self::normalized(
+      a * ((a + 1.) - (a - 1.) * cos + k),
+      2. * a * ((a - 1.) - (a + 1.) * cos),
+      a * ((a + 1.) - (a - 1.) * cos - k),
+      (a + 1.) + (a - 1.) * cos + k,
+      -2. * ((a - 1.) + (a + 1.) * cos),
+      (a + 1.) + (a - 1.) * cos - k,
+    )
+  }
+  pub fn high_shelf(
+    frequency: f64,
+    q: f64,
+    gain_db: f64,
+    sample_rate: f64,
+  ) -> Self {
+    let (cos, sin) = Self::omega(frequency, sample_rate);
+    let alpha = sin / (2. * q);
+    let a = 10f64.powf(gain_db / 40.);
+    let k = 2. * a.sqrt() * alpha;
+    Self::normalized(
+      a * ((a + 1.) + (a - 1.) * cos + k),
+      -2. * a * ((a - 1.) + (a + 1.) * cos),
+      a * ((a + 1.) + (a - 1.) * cos - k),
+      (a + 1.) - (a - 1.) * cos + k,
+      2. * ((a - 1.) - (a + 1.) * cos),
+      (a + 1.) - (a - 1.) * cos - k,
+    )
+  }
+  pub fn magnitude(&self, frequency: f64, sample_rate: f64) -> f64 {
+    let w = TAU * frequency / sample_rate;
+    let (cos_1, sin_1) = (w.cos(), w.sin());
+    let (cos_2, sin_2) = ((2. * w).cos(), (2. * w).sin());
+    let numerator_re = self.b0 + self.b1 * cos_1 + self.b2 * cos_2;
+    let numerator_im = -(self.b1 * sin_1 + self.b2 * sin_2);
+    let denominator_re = 1. + self.a1 * cos_1 + self.a2 * cos_2;
+    let denominator_im = -(self.a1 * sin_1 + self.a2 * sin_2);
+    (numerator_re.hypot(numerator_im)) / denominator_re.hypot(denominator_im)
+  }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct BiquadState {
+  x1: f64,
+  x2: f64,
+  y1: f64,
+  y2: f64,
+}
+
+impl BiquadState {
+  pub fn new() -> Self {
+    Self::default()
+  }
+  pub fn process(&mut self, c: &BiquadCoefficients, x: f64) -> f64 {
+    let y = c.b0 * x + c.b1 * self.x1 + c.b2 * self.x2
+      - c.a1 * self.y1
+      - c.a2 * self.y2;
+    self.x2 = self.x1;
+    self.x1 = x;
+    self.y2 = self.y1;
+    self.y1 = y;
+    y
+  }
+}
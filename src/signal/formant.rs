@@ -0,0 +1,145 @@
+use crate::util::{db_to_amplitude, mix, SampleClock};
+use crate::{derive_signal_ops, BiquadCoefficients, BiquadState, Signal};
+
+// (frequency, gain in dB, bandwidth) for the five formants of the vowels
+// a, e, i, o, u, taken from the classic Csound formant tables.
+type FormantTable = [[(f64, f64, f64); 5]; 5];
+
+const MALE_FORMANTS: FormantTable = [
+  [
+    (600., 0., 60.),
+    (1040., -7., 70.),
+    (2250., -9., 110.),
+    (2450., -9., 120.),
+    (2750., -20., 130.),
+  ],
+  [
+    (400., 0., 40.),
+    (1620., -12., 80.),
+    (2400., -9., 100.),
+    (2800., -12., 120.),
+    (3100., -18., 120.),
+  ],
+  [
+    (250., 0., 60.),
+    (1750., -30., 90.),
+    (2600., -16., 100.),
+    (3050., -22., 120.),
+    (3340., -28., 120.),
+  ],
+  [
+    (400., 0., 40.),
+    (750., -11., 80.),
+    (2400., -21., 100.),
+    (2600., -20., 120.),
+    (2900., -40., 120.),
+  ],
+  [
+    (350., 0., 40.),
+    (600., -20., 80.),
+    (2400., -32., 100.),
+    (2675., -28., 120.),
+    (2950., -36., 120.),
+  ],
+];
+
+const FEMALE_FORMANTS: FormantTable = [
+  [
+    (800., 0., 80.),
+    (1150., -6., 90.),
+    (2900., -32., 120.),
+    (3900., -20., 130.),
+    (4950., -50., 140.),
+  ],
+  [
+    (350., 0., 60.),
+    (2000., -20., 100.),
+    (2800., -15., 120.),
+    (3600., -40., 150.),
+    (4950., -56., 200.),
+  ],
+  [
+    (270., 0., 60.),
+    (2140., -12., 90.),
+    (2950., -26., 100.),
+    (3900., -26., 120.),
+    (4950., -44., 120.),
+  ],
+  [
+    (450., 0., 70.),
+    (800., -11., 80.),
+    (2830., -22., 100.),
+    (3800., -22., 130.),
+    (4950., -50., 135.),
+  ],
+  [
+    (325., 0., 50.),
+    (700., -16., 60.),
+    (2700., -35., 170.),
+    (3800., -40., 180.),
+    (4950., -60., 200.),
+  ],
+];
+
+#[derive(Debug, Clone, Copy)]
+pub enum FormantVoice {
+  Male,
+  Female,
+}
+
+impl FormantVoice {
+  fn table(&self) -> &'static FormantTable {
+    match self {
+      FormantVoice::Male => &MALE_FORMANTS,
+      FormantVoice::Female => &FEMALE_FORMANTS,
+    }
+  }
+}
+
+// `vowel` sweeps through a, e, i, o, u as it goes from 0 to 1.
+pub struct Formant<V: Signal, S: Signal> {
+  vowel: V,
+  voice: FormantVoice,
+  bands: [BiquadState; 5],
+  clock: SampleClock,
+  child: S,
+}
+derive_signal_ops!(Formant<V: Signal, S: Signal>);
+impl<V: Signal, S: Signal> Formant<V, S> {
+  pub fn new(voice: FormantVoice, vowel: V, child: S) -> Self {
+    Self {
+      vowel,
+      voice,
+      bands: Default::default(),
+      clock: SampleClock::new(),
+      child,
+    }
+  }
+}
+impl<V: Signal, S: Signal> Signal for Formant<V, S> {
+  fn sample(&mut self, t: f64) -> f64 {
+    let sample_rate = 1. / self.clock.tick(t);
+    let position = self.vowel.sample(t).clamp(0., 1.) * 4.;
+    let index = (position.floor() as usize).min(3);
+    let p = position - index as f64;
+    let table = self.voice.table();
+    let x = self.child.sample(t);
+    self
+      .bands
+      .iter_mut()
+      .enumerate()
+      .map(|(band_index, band)| {
+        let (frequency_1, gain_1, bandwidth_1) = table[index][band_index];
+        let (frequency_2, gain_2, bandwidth_2) = table[index + 1][band_index];
+        let frequency = mix(frequency_1, frequency_2, p);
+        let bandwidth = mix(bandwidth_1, bandwidth_2, p);
+        let coefficients = BiquadCoefficients::band_pass(
+          frequency,
+          frequency / bandwidth,
+          sample_rate,
+        );
+        db_to_amplitude(mix(gain_1, gain_2, p)) * band.process(&coefficients, x)
+      })
+      .sum()
+  }
+}
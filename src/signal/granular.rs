@@ -0,0 +1,154 @@
+use rand::Rng;
+
+use crate::util::{mix, SampleClock};
+use crate::{derive_signal_ops, Signal};
+
+#[derive(Debug, Clone, Copy)]
+pub enum GrainWindow {
+  Hann,
+  // the fraction of the grain spent fading in and out
+  Tukey(f64),
+  Trapezoid(f64),
+}
+
+impl GrainWindow {
+  fn amplitude(&self, p: f64) -> f64 {
+    match *self {
+      GrainWindow::Hann => 0.5 - 0.5 * (std::f64::consts::TAU * p).cos(),
+      GrainWindow::Tukey(fade) => {
+        let edge = 0.5 * fade.clamp(0., 1.);
+        let distance = p.min(1. - p);
+        if distance >= edge {
+          1.
+        } else {
+          0.5 - 0.5 * (std::f64::consts::PI * distance / edge).cos()
+        }
+      }
+      GrainWindow::Trapezoid(fade) => {
+        let edge = 0.5 * fade.clamp(0., 1.);
+        let distance = p.min(1. - p);
+        if distance >= edge {
+          1.
+        } else {
+          distance / edge
+        }
+      }
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Grain {
+  position: f64,
+  rate: f64,
+  age: f64,
+  length: f64,
+}
+
+// Granular playback of a buffer. Grain size and spray are in seconds,
+// density in grains per second, position from 0 to 1 across the buffer and
+// pitch in semitones. At most `max_grains` play at once; grains that would
+// go over that limit are skipped, so nothing allocates while running.
+pub struct Granular<Sz: Signal, D: Signal, Po: Signal, Pi: Signal, Sp: Signal> {
+  buffer: Vec<f64>,
+  buffer_sample_rate: f64,
+  size: Sz,
+  density: D,
+  position: Po,
+  pitch: Pi,
+  spray: Sp,
+  window: GrainWindow,
+  grains: Vec<Grain>,
+  max_grains: usize,
+  phase: f64,
+  clock: SampleClock,
+}
+derive_signal_ops!(Granular<Sz: Signal, D: Signal, Po: Signal, Pi: Signal, Sp: Signal>);
+impl<Sz: Signal, D: Signal, Po: Signal, Pi: Signal, Sp: Signal>
+  Granular<Sz, D, Po, Pi, Sp>
+{
+  pub fn new(
+    buffer: Vec<f64>,
+    buffer_sample_rate: f64,
+    size: Sz,
+    density: D,
+    position: Po,
+    pitch: Pi,
+    spray: Sp,
+  ) -> Self {
+    Self {
+      buffer,
+      buffer_sample_rate,
+      size,
+      density,
+      position,
+      pitch,
+      spray,
+      window: GrainWindow::Hann,
+      grains: Vec::with_capacity(64),
+      max_grains: 64,
+      phase: 1.,
+      clock: SampleClock::new(),
+    }
+  }
+  pub fn with_window(mut self, window: GrainWindow) -> Self {
+    self.window = window;
+    self
+  }
+  pub fn with_max_grains(mut self, max_grains: usize) -> Self {
+    self.max_grains = max_grains;
+    self.grains = Vec::with_capacity(max_grains);
+    self
+  }
+  fn read(&self, position: f64) -> f64 {
+    let length = self.buffer.len();
+    let position = position.rem_euclid(length as f64);
+    let i = position.floor() as usize % length;
+    mix(
+      self.buffer[i],
+      self.buffer[(i + 1) % length],
+      position - position.floor(),
+    )
+  }
+}
+impl<Sz: Signal, D: Signal, Po: Signal, Pi: Signal, Sp: Signal> Signal
+  for Granular<Sz, D, Po, Pi, Sp>
+{
+  fn sample(&mut self, t: f64) -> f64 {
+    let dt = self.clock.tick(t);
+    let size = self.size.sample(t).max(dt);
+    let density = self.density.sample(t).max(0.);
+    let position = self.position.sample(t).clamp(0., 1.);
+    let pitch = self.pitch.sample(t);
+    let spray = self.spray.sample(t).max(0.);
+    if self.buffer.is_empty() {
+      return 0.;
+    }
+
+    self.phase += density * dt;
+    if self.phase >= 1. {
+      self.phase %= 1.;
+      if self.grains.len() < self.max_grains {
+        let offset = spray * (rand::thread_rng().gen::<f64>() * 2. - 1.);
+        self.grains.push(Grain {
+          position: position * self.buffer.len() as f64
+            + offset * self.buffer_sample_rate,
+          rate: (pitch / 12.).exp2() * self.buffer_sample_rate * dt,
+          age: 0.,
+          length: size / dt,
+        });
+      }
+    }
+
+    let mut sum = 0.;
+    for grain in self.grains.iter() {
+      sum += self.window.amplitude(grain.age / grain.length)
+        * self.read(grain.position + grain.age * grain.rate);
+    }
+    for grain in self.grains.iter_mut() {
+      grain.age += 1.;
+    }
+    self.grains.retain(|grain| grain.age < grain.length);
+    sum
+  }
+}
@@ -0,0 +1,164 @@
+use std::f64::consts::{PI, TAU};
+
+use rand::Rng;
+
+use crate::util::SampleClock;
+use crate::{derive_signal_ops, tri, Const, Signal};
+
+#[derive(Debug, Clone, Copy)]
+pub enum LfoShape {
+  Sine,
+  Tri,
+  Saw,
+  Square,
+  // holds a new random value each cycle
+  SampleAndHold,
+  // glides smoothly between a new random value each cycle
+  SmoothRandom,
+}
+
+fn random_bipolar() -> f64 {
+  rand::thread_rng().gen::<f64>() * 2. - 1.
+}
+
+// A low frequency oscillator with bipolar output by default. `rate` is in Hz,
+// or in beats per cycle when synced to a tempo.
+pub struct Lfo<R: Signal> {
+  shape: LfoShape,
+  rate: R,
+  beat_duration: Option<Box<dyn Signal>>,
+  reset: Option<Box<dyn Signal>>,
+  reset_on: bool,
+  unipolar: bool,
+  delay: f64,
+  fade: f64,
+  clock: SampleClock,
+  phase: f64,
+  elapsed: f64,
+  // the random values at the start and end of the current cycle
+  held: f64,
+  next: f64,
+}
+derive_signal_ops!(Lfo<R: Signal>);
+impl<R: Signal> Lfo<R> {
+  pub fn new(shape: LfoShape, rate: R) -> Self {
+    Self {
+      shape,
+      rate,
+      beat_duration: None,
+      reset: None,
+      reset_on: false,
+      unipolar: false,
+      delay: 0.,
+      fade: 0.,
+      clock: SampleClock::new(),
+      phase: 0.,
+      elapsed: 0.,
+      held: random_bipolar(),
+      next: random_bipolar(),
+    }
+  }
+  // outputs from 0 to 1 rather than from -1 to 1
+  pub fn unipolar(mut self) -> Self {
+    self.unipolar = true;
+    self
+  }
+  // measures `rate` as the number of beats per cycle, given the beat length
+  // in seconds, e.g. the spacing of a `GateBeat` clock
+  pub fn with_tempo_sync<B: Signal + 'static>(
+    mut self,
+    beat_duration: B,
+  ) -> Self {
+    self.beat_duration = Some(Box::new(beat_duration));
+    self
+  }
+  // restarts the cycle, along with any delay and fade-in, whenever `gate`
+  // opens
+  pub fn with_reset<G: Signal + 'static>(mut self, gate: G) -> Self {
+    self.reset = Some(Box::new(gate));
+    self
+  }
+  // stays silent for `delay` seconds, then fades in over `fade` seconds
+  pub fn with_fade_in(mut self, delay: f64, fade: f64) -> Self {
+    self.delay = delay;
+    self.fade = fade;
+    self
+  }
+  fn shape_value(&self) -> f64 {
+    let phase = self.phase;
+    match self.shape {
+      LfoShape::Sine => (phase * TAU).sin(),
+      LfoShape::Tri => tri(phase),
+      LfoShape::Saw => phase * 2. - 1.,
+      LfoShape::Square => {
+        if phase > 0.5 {
+          1.
+        } else {
+          -1.
+        }
+      }
+      LfoShape::SampleAndHold => self.held,
+      LfoShape::SmoothRandom => {
+        let p = 0.5 - 0.5 * (phase * PI).cos();
+        self.held + (self.next - self.held) * p
+      }
+    }
+  }
+}
+impl Lfo<Const> {
+  pub fn constant(shape: LfoShape, rate: f64) -> Self {
+    Self::new(shape, Const(rate))
+  }
+}
+impl<R: Signal> Signal for Lfo<R> {
+  fn sample(&mut self, t: f64) -> f64 {
+    let dt = self.clock.tick(t);
+    let rate = self.rate.sample(t);
+    let frequency = match &mut self.beat_duration {
+      Some(beat_duration) => {
+        let cycle = rate * beat_duration.sample(t);
+        if cycle > 0. {
+          1. / cycle
+        } else {
+          0.
+        }
+      }
+      None => rate,
+    };
+    let reset = match &mut self.reset {
+      Some(gate) => {
+        let on = gate.sample(t) > 0.5;
+        let rising = on && !self.reset_on;
+        self.reset_on = on;
+        rising
+      }
+      None => false,
+    };
+    if reset {
+      self.phase = 0.;
+      self.elapsed = 0.;
+    } else {
+      self.elapsed += dt;
+      self.phase += frequency * dt;
+      if self.phase >= 1. || self.phase < 0. {
+        self.phase = self.phase.rem_euclid(1.);
+        self.held = self.next;
+        self.next = random_bipolar();
+      }
+    }
+    let fade = if self.elapsed < self.delay {
+      0.
+    } else if self.fade > 0. {
+      ((self.elapsed - self.delay) / self.fade).min(1.)
+    } else {
+      1.
+    };
+    let value = self.shape_value();
+    let value = if self.unipolar {
+      (value + 1.) * 0.5
+    } else {
+      value
+    };
+    fade * value
+  }
+}
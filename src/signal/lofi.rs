@@ -0,0 +1,59 @@
+use rand::Rng;
+
+use crate::util::SampleClock;
+use crate::{derive_signal_ops, Signal};
+
+// Quantizes to `bits` bits over the range -1 to 1. Fractional bit depths
+// are allowed, so the depth can be swept smoothly.
+pub struct BitCrush<B: Signal, S: Signal>(pub B, pub S);
+derive_signal_ops!(BitCrush<B: Signal, S: Signal>);
+impl<B: Signal, S: Signal> Signal for BitCrush<B, S> {
+  fn sample(&mut self, t: f64) -> f64 {
+    let levels = (self.0.sample(t).max(1.) - 1.).exp2();
+    (self.1.sample(t) * levels).round() / levels
+  }
+}
+
+// Samples and holds the child at `rate` Hz. With jitter, each hold lasts a
+// random amount longer or shorter, by up to `jitter` times the hold period.
+pub struct Decimate<R: Signal, S: Signal> {
+  rate: R,
+  child: S,
+  jitter: f64,
+  phase: f64,
+  next_threshold: f64,
+  held: f64,
+  clock: SampleClock,
+}
+derive_signal_ops!(Decimate<R: Signal, S: Signal>);
+impl<R: Signal, S: Signal> Decimate<R, S> {
+  pub fn new(rate: R, child: S) -> Self {
+    Self {
+      rate,
+      child,
+      jitter: 0.,
+      phase: 1.,
+      next_threshold: 1.,
+      held: 0.,
+      clock: SampleClock::new(),
+    }
+  }
+  pub fn with_jitter(mut self, jitter: f64) -> Self {
+    self.jitter = jitter.clamp(0., 1.);
+    self
+  }
+}
+impl<R: Signal, S: Signal> Signal for Decimate<R, S> {
+  fn sample(&mut self, t: f64) -> f64 {
+    let dt = self.clock.tick(t);
+    let x = self.child.sample(t);
+    self.phase += self.rate.sample(t).max(0.) * dt;
+    if self.phase >= self.next_threshold {
+      self.phase = (self.phase - self.next_threshold).min(1.);
+      self.next_threshold =
+        1. + self.jitter * (rand::thread_rng().gen::<f64>() * 2. - 1.);
+      self.held = x;
+    }
+    self.held
+  }
+}
@@ -2,6 +2,8 @@ use std::sync::Arc;
 use std::sync::Mutex;
 
 use crate::derive_signal_ops;
+use crate::util::mix;
+use crate::EnvelopeCurve;
 use crate::Signal;
 
 use crate::MidiLedger;
@@ -37,6 +39,34 @@ impl Signal for MidiNote {
   }
 }
 
+// The velocity of the most recent press of `note`, from 0 to 1. It keeps its
+// value after the note is released, so envelopes can use it through their
+// release stage.
+#[derive(Debug, Clone)]
+pub struct MidiVelocity {
+  note: u8,
+  ledger: Arc<Mutex<MidiLedger>>,
+}
+derive_signal_ops!(MidiVelocity);
+impl MidiVelocity {
+  pub fn new(note: u8, ledger: &Arc<Mutex<MidiLedger>>) -> Self {
+    Self {
+      note,
+      ledger: ledger.clone(),
+    }
+  }
+}
+impl Signal for MidiVelocity {
+  fn sample(&mut self, _t: f64) -> f64 {
+    let ledger = self.ledger.lock().unwrap();
+    ledger
+      .notes
+      .get(&self.note)
+      .map(|note| note.velocity)
+      .unwrap_or(0.)
+  }
+}
+
 #[derive(Debug, Clone)]
 pub struct MidiModWheel {
   ledger: Arc<Mutex<MidiLedger>>,
@@ -70,3 +100,47 @@ impl Signal for MidiPitchBend {
     self.ledger.lock().unwrap().pitch_bend
   }
 }
+
+// A MIDI controller's value, shaped by a response curve and then mapped onto
+// a range, which is 0 to 1 by default.
+#[derive(Debug, Clone)]
+pub struct MidiCC {
+  channel: u8,
+  cc: u8,
+  ledger: Arc<Mutex<MidiLedger>>,
+  min: f64,
+  max: f64,
+  curve: EnvelopeCurve,
+}
+derive_signal_ops!(MidiCC);
+impl MidiCC {
+  pub fn new(channel: u8, cc: u8, ledger: &Arc<Mutex<MidiLedger>>) -> Self {
+    Self {
+      channel,
+      cc,
+      ledger: ledger.clone(),
+      min: 0.,
+      max: 1.,
+      curve: EnvelopeCurve::Linear,
+    }
+  }
+  pub fn with_range(mut self, min: f64, max: f64) -> Self {
+    self.min = min;
+    self.max = max;
+    self
+  }
+  pub fn with_curve(mut self, curve: EnvelopeCurve) -> Self {
+    self.curve = curve;
+    self
+  }
+}
+impl Signal for MidiCC {
+  fn sample(&mut self, _t: f64) -> f64 {
+    let value = self
+      .ledger
+      .lock()
+      .unwrap()
+      .controller(self.channel, self.cc);
+    mix(self.min, self.max, self.curve.shape(value, true))
+  }
+}
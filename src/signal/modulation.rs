@@ -1,3 +1,4 @@
+use crate::util::SampleClock;
 use crate::{derive_signal_ops, Signal};
 
 #[derive(Debug, Clone)]
@@ -40,3 +41,115 @@ impl<C: Signal, M: Signal> Signal for FreqMod<C, M> {
     self.carrier.sample(self.integrated_t)
   }
 }
+
+// Crossfades between ring modulation (bias 0) and classic AM (bias 1), where
+// the carrier is always present at full level.
+#[derive(Debug, Clone)]
+pub struct AmplitudeMod<B: Signal, M: Signal, C: Signal>(pub B, pub M, pub C);
+derive_signal_ops!(AmplitudeMod<B: Signal, M: Signal, C: Signal>);
+impl<B: Signal, M: Signal, C: Signal> Signal for AmplitudeMod<B, M, C> {
+  fn sample(&mut self, t: f64) -> f64 {
+    let bias = self.0.sample(t).max(0.);
+    self.2.sample(t) * (bias + self.1.sample(t)) / (1. + bias)
+  }
+}
+
+// Olli Niemitalo's all-pass coefficients, giving two outputs that stay within
+// about a degree of 90 degrees apart over most of the audible range.
+const HILBERT_IN_PHASE: [f64; 4] =
+  [0.6923878, 0.9360654322959, 0.9882295226860, 0.9987488452737];
+const HILBERT_QUADRATURE: [f64; 4] = [
+  0.4021921162426,
+  0.8561710882420,
+  0.9722909545651,
+  0.9952884791278,
+];
+
+#[derive(Debug, Clone)]
+struct AllPassChain {
+  coefficients: [f64; 4],
+  // (x[n-1], x[n-2], y[n-1], y[n-2]) for each stage
+  states: [(f64, f64, f64, f64); 4],
+}
+
+impl AllPassChain {
+  fn new(coefficients: [f64; 4]) -> Self {
+    Self {
+      coefficients: coefficients.map(|a| a * a),
+      states: [(0., 0., 0., 0.); 4],
+    }
+  }
+  fn process(&mut self, x: f64) -> f64 {
+    self.coefficients.iter().zip(self.states.iter_mut()).fold(
+      x,
+      |x, (a, (x_1, x_2, y_1, y_2))| {
+        let y = a * (x + *y_2) - *x_2;
+        (*x_2, *x_1, *y_2, *y_1) = (*x_1, x, *y_1, y);
+        y
+      },
+    )
+  }
+}
+
+// Splits the child into the real and imaginary parts of its analytic signal.
+// Used as a plain `Signal` it outputs the real part.
+#[derive(Debug, Clone)]
+pub struct Hilbert<S: Signal> {
+  child: S,
+  in_phase: AllPassChain,
+  quadrature: AllPassChain,
+  last_in_phase: f64,
+}
+derive_signal_ops!(Hilbert<S: Signal>);
+impl<S: Signal> Hilbert<S> {
+  pub fn new(child: S) -> Self {
+    Self {
+      child,
+      in_phase: AllPassChain::new(HILBERT_IN_PHASE),
+      quadrature: AllPassChain::new(HILBERT_QUADRATURE),
+      last_in_phase: 0.,
+    }
+  }
+  pub fn sample_analytic(&mut self, t: f64) -> (f64, f64) {
+    let x = self.child.sample(t);
+    let real = self.last_in_phase;
+    self.last_in_phase = self.in_phase.process(x);
+    let imaginary = self.quadrature.process(x);
+    (real, imaginary)
+  }
+}
+impl<S: Signal> Signal for Hilbert<S> {
+  fn sample(&mut self, t: f64) -> f64 {
+    self.sample_analytic(t).0
+  }
+}
+
+// Single-sideband (Bode-style) shifter: moves every partial of the child up
+// by `shift` Hz, or down for negative shifts, which breaks harmonic ratios.
+#[derive(Debug, Clone)]
+pub struct FrequencyShifter<F: Signal, S: Signal> {
+  shift: F,
+  hilbert: Hilbert<S>,
+  phase: f64,
+  clock: SampleClock,
+}
+derive_signal_ops!(FrequencyShifter<F: Signal, S: Signal>);
+impl<F: Signal, S: Signal> FrequencyShifter<F, S> {
+  pub fn new(shift: F, child: S) -> Self {
+    Self {
+      shift,
+      hilbert: Hilbert::new(child),
+      phase: 0.,
+      clock: SampleClock::new(),
+    }
+  }
+}
+impl<F: Signal, S: Signal> Signal for FrequencyShifter<F, S> {
+  fn sample(&mut self, t: f64) -> f64 {
+    let dt = self.clock.tick(t);
+    self.phase = (self.phase + self.shift.sample(t) * dt).rem_euclid(1.);
+    let (real, imaginary) = self.hilbert.sample_analytic(t);
+    let angle = std::f64::consts::TAU * self.phase;
+    real * angle.cos() + imaginary * angle.sin()
+  }
+}
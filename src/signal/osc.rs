@@ -36,7 +36,7 @@ impl Signal for Square {
 }
 
 pub fn tri(mut x: f64) -> f64 {
-  x %= 1.;
+  x = x.rem_euclid(1.);
   4. * x.min((0.5 - x).max(x - 1.))
 }
 
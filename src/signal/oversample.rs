@@ -0,0 +1,202 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use crate::util::SampleClock;
+use crate::{derive_signal_ops, Signal};
+
+const HALF_BAND_CENTER: usize = 15;
+
+// The even taps of a 31-tap windowed-sinc half-band lowpass. All odd taps
+// other than the center (0.5) are zero, so each 2x stage splits into one
+// short FIR branch and one pure delay branch.
+fn half_band_taps() -> Vec<f64> {
+  (0..=HALF_BAND_CENTER)
+    .map(|j| {
+      let n = 2 * j;
+      let x = n as f64 - HALF_BAND_CENTER as f64;
+      let sinc =
+        (0.5 * std::f64::consts::PI * x).sin() / (std::f64::consts::PI * x);
+      let length = 2 * HALF_BAND_CENTER;
+      let window = 0.42
+        - 0.5 * (std::f64::consts::TAU * n as f64 / length as f64).cos()
+        + 0.08 * (2. * std::f64::consts::TAU * n as f64 / length as f64).cos();
+      sinc * window
+    })
+    .collect()
+}
+
+#[derive(Debug, Clone)]
+struct HalfBandDecimator {
+  taps: Vec<f64>,
+  even_history: VecDeque<f64>,
+  odd_history: VecDeque<f64>,
+}
+
+impl HalfBandDecimator {
+  fn new() -> Self {
+    let taps = half_band_taps();
+    Self {
+      even_history: vec![0.; taps.len()].into(),
+      odd_history: vec![0.; HALF_BAND_CENTER / 2 + 1].into(),
+      taps,
+    }
+  }
+  fn process(&mut self, older: f64, newer: f64) -> f64 {
+    self.even_history.pop_back();
+    self.even_history.push_front(newer);
+    self.odd_history.pop_back();
+    self.odd_history.push_front(older);
+    let fir: f64 = self
+      .taps
+      .iter()
+      .zip(self.even_history.iter())
+      .map(|(h, x)| h * x)
+      .sum();
+    fir + 0.5 * self.odd_history.back().unwrap()
+  }
+}
+
+#[derive(Debug, Clone)]
+struct HalfBandInterpolator {
+  taps: Vec<f64>,
+  history: VecDeque<f64>,
+}
+
+impl HalfBandInterpolator {
+  fn new() -> Self {
+    let taps = half_band_taps();
+    Self {
+      history: vec![0.; taps.len()].into(),
+      taps,
+    }
+  }
+  fn process(&mut self, x: f64) -> (f64, f64) {
+    self.history.pop_back();
+    self.history.push_front(x);
+    let fir: f64 = self
+      .taps
+      .iter()
+      .zip(self.history.iter())
+      .map(|(h, x)| h * x)
+      .sum();
+    (2. * fir, self.history[(HALF_BAND_CENTER - 1) / 2])
+  }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum OversamplingFactor {
+  X2,
+  X4,
+  X8,
+}
+
+impl OversamplingFactor {
+  fn stages(&self) -> usize {
+    match self {
+      OversamplingFactor::X2 => 1,
+      OversamplingFactor::X4 => 2,
+      OversamplingFactor::X8 => 3,
+    }
+  }
+}
+
+// Stands in for the upsampled input inside a subgraph built with
+// `Oversampled::process`.
+#[derive(Debug, Clone)]
+pub struct OversampledInput {
+  value: Arc<Mutex<f64>>,
+}
+derive_signal_ops!(OversampledInput);
+impl Signal for OversampledInput {
+  fn sample(&mut self, _t: f64) -> f64 {
+    *self.value.lock().unwrap()
+  }
+}
+
+struct UpsampledSource {
+  placeholder: OversampledInput,
+  signal: Box<dyn Signal>,
+  interpolators: Vec<HalfBandInterpolator>,
+}
+
+// Runs `child` at a multiple of the sample rate by sampling it at evenly
+// spaced times within each output sample, then brings it back down through
+// cascaded half-band filters.
+pub struct Oversampled<S: Signal> {
+  child: S,
+  source: Option<UpsampledSource>,
+  decimators: Vec<HalfBandDecimator>,
+  factor: usize,
+  buffer: Vec<f64>,
+  scratch: Vec<f64>,
+  clock: SampleClock,
+}
+derive_signal_ops!(Oversampled<S: Signal>);
+impl<S: Signal> Oversampled<S> {
+  pub fn new(factor: OversamplingFactor, child: S) -> Self {
+    let stages = factor.stages();
+    Self {
+      child,
+      source: None,
+      decimators: (0..stages).map(|_| HalfBandDecimator::new()).collect(),
+      factor: 1 << stages,
+      buffer: vec![0.; 1 << stages],
+      scratch: vec![0.; 1 << stages],
+      clock: SampleClock::new(),
+    }
+  }
+  // Upsamples `input`, which runs at the base rate, and feeds it into the
+  // subgraph built by `f`, e.g. `|x| Sigmoid(6. * Wavefold(x))`.
+  pub fn process<I: Signal + 'static, F: FnOnce(OversampledInput) -> S>(
+    factor: OversamplingFactor,
+    input: I,
+    f: F,
+  ) -> Self {
+    let placeholder = OversampledInput {
+      value: Arc::new(Mutex::new(0.)),
+    };
+    let mut oversampled = Self::new(factor, f(placeholder.clone()));
+    oversampled.source = Some(UpsampledSource {
+      placeholder,
+      signal: Box::new(input),
+      interpolators: (0..factor.stages())
+        .map(|_| HalfBandInterpolator::new())
+        .collect(),
+    });
+    oversampled
+  }
+}
+impl<S: Signal> Signal for Oversampled<S> {
+  fn sample(&mut self, t: f64) -> f64 {
+    let dt = self.clock.tick(t);
+    let mut length = 1;
+    if let Some(source) = &mut self.source {
+      self.buffer[0] = source.signal.sample(t);
+      for interpolator in source.interpolators.iter_mut() {
+        for i in 0..length {
+          let (older, newer) = interpolator.process(self.buffer[i]);
+          self.scratch[2 * i] = older;
+          self.scratch[2 * i + 1] = newer;
+        }
+        length *= 2;
+        self.buffer[..length].copy_from_slice(&self.scratch[..length]);
+      }
+    }
+    for i in 0..self.factor {
+      let sub_t = t - dt * (self.factor - 1 - i) as f64 / self.factor as f64;
+      if let Some(source) = &self.source {
+        *source.placeholder.value.lock().unwrap() = self.buffer[i];
+      }
+      self.buffer[i] = self.child.sample(sub_t);
+    }
+    length = self.factor;
+    for decimator in self.decimators.iter_mut() {
+      length /= 2;
+      for i in 0..length {
+        self.buffer[i] =
+          decimator.process(self.buffer[2 * i], self.buffer[2 * i + 1]);
+      }
+    }
+    self.buffer[0]
+  }
+}
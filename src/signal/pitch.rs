@@ -0,0 +1,128 @@
+use crate::util::{resample, SampleClock};
+use crate::{derive_signal_ops, DelayInterpolation, DelayLine, Signal};
+
+fn hann(p: f64) -> f64 {
+  0.5 - 0.5 * (std::f64::consts::TAU * p).cos()
+}
+
+// Real-time shifter built from two delay taps that sweep through a short
+// window at a rate set by the pitch ratio, half a window apart and
+// crossfaded so that each tap is silent as it jumps back.
+pub struct PitchShifter<P: Signal, S: Signal> {
+  semitones: P,
+  child: S,
+  line: DelayLine,
+  window: f64,
+  phase: f64,
+  clock: SampleClock,
+}
+derive_signal_ops!(PitchShifter<P: Signal, S: Signal>);
+impl<P: Signal, S: Signal> PitchShifter<P, S> {
+  pub fn new(semitones: P, child: S) -> Self {
+    Self {
+      semitones,
+      child,
+      line: DelayLine::new(0.06),
+      window: 0.05,
+      phase: 0.,
+      clock: SampleClock::new(),
+    }
+  }
+  // Longer windows smear transients less audibly on sustained material,
+  // shorter ones suit percussive material.
+  pub fn with_window(mut self, window: f64) -> Self {
+    self.window = window;
+    self.line = DelayLine::new(window + 0.01);
+    self
+  }
+}
+impl<P: Signal, S: Signal> Signal for PitchShifter<P, S> {
+  fn sample(&mut self, t: f64) -> f64 {
+    let dt = self.clock.tick(t);
+    let sample_rate = 1. / dt;
+    let ratio = (self.semitones.sample(t) / 12.).exp2();
+    self.phase = (self.phase + (1. - ratio) * dt / self.window).rem_euclid(1.);
+    let window = self.window * sample_rate;
+    let output = [self.phase, (self.phase + 0.5) % 1.]
+      .iter()
+      .map(|phase| {
+        hann(*phase)
+          * self
+            .line
+            .read(1. + phase * window, DelayInterpolation::Linear)
+      })
+      .sum();
+    self.line.push(self.child.sample(t), sample_rate);
+    output
+  }
+}
+
+// Offline WSOLA time stretching: changes the duration of `samples` by
+// `stretch` (2 is twice as long) without changing their pitch, by
+// overlap-adding windowed frames whose positions are nudged to line up with
+// the waveform of the previous frame.
+pub fn time_stretch(
+  samples: &[f64],
+  sample_rate: f64,
+  stretch: f64,
+) -> Vec<f64> {
+  let frame = ((0.04 * sample_rate) as usize).max(4) & !1;
+  let synthesis_hop = frame / 2;
+  let analysis_hop = synthesis_hop as f64 / stretch.max(1e-3);
+  let tolerance = synthesis_hop / 2;
+  let output_length = (samples.len() as f64 * stretch) as usize;
+  let mut output = vec![0.; output_length + frame];
+  let read = |i: isize| {
+    if i < 0 {
+      0.
+    } else {
+      samples.get(i as usize).copied().unwrap_or(0.)
+    }
+  };
+  let mut previous: isize = 0;
+  let mut frame_index = 0;
+  while frame_index * synthesis_hop < output_length {
+    let nominal = (frame_index as f64 * analysis_hop) as isize;
+    let position = if frame_index == 0 {
+      nominal
+    } else {
+      // the continuation the previous frame would have had
+      let target = previous + synthesis_hop as isize;
+      (-(tolerance as isize)..=tolerance as isize)
+        .map(|offset| {
+          let candidate = nominal + offset;
+          let correlation: f64 = (0..frame)
+            .step_by(4)
+            .map(|i| read(candidate + i as isize) * read(target + i as isize))
+            .sum();
+          (candidate, correlation)
+        })
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(candidate, _)| candidate)
+        .unwrap_or(nominal)
+    };
+    let start = frame_index * synthesis_hop;
+    for i in 0..frame {
+      output[start + i] +=
+        hann(i as f64 / frame as f64) * read(position + i as isize);
+    }
+    previous = position;
+    frame_index += 1;
+  }
+  output.truncate(output_length);
+  output
+}
+
+// Offline pitch shift that keeps the duration: time stretches by the pitch
+// ratio, then resamples back to the original length.
+pub fn pitch_shift(
+  samples: &[f64],
+  sample_rate: f64,
+  semitones: f64,
+) -> Vec<f64> {
+  let ratio = (semitones / 12.).exp2();
+  let stretched = time_stretch(samples, sample_rate, ratio);
+  let mut shifted = resample(&stretched, sample_rate * ratio, sample_rate);
+  shifted.truncate(samples.len());
+  shifted
+}
@@ -0,0 +1,488 @@
+use crate::util::SampleClock;
+use crate::{
+  derive_signal_ops, Const, DelayInterpolation, DelayLine, Signal, StereoSignal,
+};
+
+// Delay lengths are given in samples at 44.1kHz, as in the original
+// designs, and rescaled to the running sample rate.
+const REFERENCE_SAMPLE_RATE: f64 = 44100.;
+
+fn scaled_length(samples: f64, sample_rate: f64) -> f64 {
+  (samples * sample_rate / REFERENCE_SAMPLE_RATE)
+    .round()
+    .max(1.)
+}
+
+#[derive(Debug, Clone)]
+struct LowPassComb {
+  line: DelayLine,
+  length: f64,
+  filter_store: f64,
+}
+
+impl LowPassComb {
+  fn new(length: f64) -> Self {
+    Self {
+      line: DelayLine::new(2. * length / REFERENCE_SAMPLE_RATE),
+      length,
+      filter_store: 0.,
+    }
+  }
+  fn process(
+    &mut self,
+    input: f64,
+    feedback: f64,
+    damping: f64,
+    sample_rate: f64,
+  ) -> f64 {
+    let output = self.line.read(
+      scaled_length(self.length, sample_rate),
+      DelayInterpolation::Linear,
+    );
+    self.filter_store = output * (1. - damping) + self.filter_store * damping;
+    self
+      .line
+      .push(input + self.filter_store * feedback, sample_rate);
+    output
+  }
+}
+
+#[derive(Debug, Clone)]
+struct SchroederAllPass {
+  line: DelayLine,
+  length: f64,
+}
+
+impl SchroederAllPass {
+  fn new(length: f64) -> Self {
+    Self {
+      line: DelayLine::new(2. * length / REFERENCE_SAMPLE_RATE),
+      length,
+    }
+  }
+  fn process(&mut self, input: f64, feedback: f64, sample_rate: f64) -> f64 {
+    let delayed = self.line.read(
+      scaled_length(self.length, sample_rate),
+      DelayInterpolation::Linear,
+    );
+    self.line.push(input + delayed * feedback, sample_rate);
+    delayed - input
+  }
+}
+
+const FREEVERB_COMB_LENGTHS: [f64; 8] =
+  [1116., 1188., 1277., 1356., 1422., 1491., 1557., 1617.];
+const FREEVERB_ALL_PASS_LENGTHS: [f64; 4] = [556., 441., 341., 225.];
+const FREEVERB_STEREO_SPREAD: f64 = 23.;
+const FREEVERB_FIXED_GAIN: f64 = 0.015;
+const FREEVERB_SCALE_WET: f64 = 3.;
+const FREEVERB_SCALE_DAMPING: f64 = 0.4;
+const FREEVERB_SCALE_ROOM: f64 = 0.28;
+const FREEVERB_OFFSET_ROOM: f64 = 0.7;
+
+// Room size, damping, mix and width all run from 0 to 1. As a mono `Signal`
+// it outputs the average of the two channels.
+pub struct Freeverb<R: Signal, D: Signal, M: Signal, W: Signal, S: Signal> {
+  room_size: R,
+  damping: D,
+  mix: M,
+  width: W,
+  child: S,
+  combs: [Vec<LowPassComb>; 2],
+  all_passes: [Vec<SchroederAllPass>; 2],
+  clock: SampleClock,
+}
+derive_signal_ops!(Freeverb<R: Signal, D: Signal, M: Signal, W: Signal, S: Signal>);
+impl<R: Signal, D: Signal, M: Signal, W: Signal, S: Signal>
+  Freeverb<R, D, M, W, S>
+{
+  pub fn new(room_size: R, damping: D, mix: M, width: W, child: S) -> Self {
+    let channel = |spread: f64| {
+      (
+        FREEVERB_COMB_LENGTHS
+          .iter()
+          .map(|length| LowPassComb::new(length + spread))
+          .collect(),
+        FREEVERB_ALL_PASS_LENGTHS
+          .iter()
+          .map(|length| SchroederAllPass::new(length + spread))
+          .collect(),
+      )
+    };
+    let (left_combs, left_all_passes) = channel(0.);
+    let (right_combs, right_all_passes) = channel(FREEVERB_STEREO_SPREAD);
+    Self {
+      room_size,
+      damping,
+      mix,
+      width,
+      child,
+      combs: [left_combs, right_combs],
+      all_passes: [left_all_passes, right_all_passes],
+      clock: SampleClock::new(),
+    }
+  }
+}
+impl<S: Signal> Freeverb<Const, Const, Const, Const, S> {
+  pub fn constant(
+    room_size: f64,
+    damping: f64,
+    mix: f64,
+    width: f64,
+    child: S,
+  ) -> Self {
+    Self::new(
+      Const(room_size),
+      Const(damping),
+      Const(mix),
+      Const(width),
+      child,
+    )
+  }
+}
+impl<R: Signal, D: Signal, M: Signal, W: Signal, S: Signal> StereoSignal
+  for Freeverb<R, D, M, W, S>
+{
+  fn sample_stereo(&mut self, t: f64) -> (f64, f64) {
+    let sample_rate = 1. / self.clock.tick(t);
+    let feedback = self.room_size.sample(t).clamp(0., 1.) * FREEVERB_SCALE_ROOM
+      + FREEVERB_OFFSET_ROOM;
+    let damping = self.damping.sample(t).clamp(0., 1.) * FREEVERB_SCALE_DAMPING;
+    let mix = self.mix.sample(t).clamp(0., 1.);
+    let width = self.width.sample(t).clamp(0., 1.);
+    let dry = self.child.sample(t);
+    let input = 2. * FREEVERB_FIXED_GAIN * dry;
+    let [left, right] = [0, 1].map(|channel| {
+      let combed = self.combs[channel]
+        .iter_mut()
+        .map(|comb| comb.process(input, feedback, damping, sample_rate))
+        .sum::<f64>();
+      self.all_passes[channel]
+        .iter_mut()
+        .fold(combed, |x, all_pass| all_pass.process(x, 0.5, sample_rate))
+    });
+    let wet = mix * FREEVERB_SCALE_WET;
+    let wet_1 = wet * (0.5 + 0.5 * width);
+    let wet_2 = wet * (0.5 - 0.5 * width);
+    (
+      left * wet_1 + right * wet_2 + dry * (1. - mix),
+      right * wet_1 + left * wet_2 + dry * (1. - mix),
+    )
+  }
+}
+impl<R: Signal, D: Signal, M: Signal, W: Signal, S: Signal> Signal
+  for Freeverb<R, D, M, W, S>
+{
+  fn sample(&mut self, t: f64) -> f64 {
+    let (left, right) = self.sample_stereo(t);
+    0.5 * (left + right)
+  }
+}
+
+const DATTORRO_SAMPLE_RATE: f64 = 29761.;
+const DATTORRO_INPUT_DIFFUSERS: [(f64, f64); 4] =
+  [(142., 0.75), (107., 0.75), (379., 0.625), (277., 0.625)];
+const DATTORRO_DECAY_DIFFUSION: f64 = 0.7;
+const DATTORRO_MAX_EXCURSION: f64 = 16.;
+const DATTORRO_MAX_PRE_DELAY: f64 = 0.5;
+const DATTORRO_LFO_RATE: f64 = 1.;
+// (modulated all-pass, delay, all-pass, delay) lengths for each half of the
+// tank
+const DATTORRO_TANK_LENGTHS: [(f64, f64, f64, f64); 2] =
+  [(672., 4453., 1800., 3720.), (908., 4217., 2656., 3163.)];
+// (tank half, line within that half, offset, sign) for each output, where
+// lines are numbered 0: first delay, 1: second all-pass, 2: second delay
+const DATTORRO_TAPS: [[(usize, usize, f64, f64); 7]; 2] = [
+  [
+    (1, 0, 266., 1.),
+    (1, 0, 2974., 1.),
+    (1, 1, 1913., -1.),
+    (1, 2, 1996., 1.),
+    (0, 0, 1990., -1.),
+    (0, 1, 187., -1.),
+    (0, 2, 1066., -1.),
+  ],
+  [
+    (0, 0, 353., 1.),
+    (0, 0, 3627., 1.),
+    (0, 1, 1228., -1.),
+    (0, 2, 2673., 1.),
+    (1, 0, 2111., -1.),
+    (1, 1, 335., -1.),
+    (1, 2, 121., -1.),
+  ],
+];
+
+fn dattorro_length(samples: f64, sample_rate: f64) -> f64 {
+  (samples * sample_rate / DATTORRO_SAMPLE_RATE)
+    .round()
+    .max(1.)
+}
+
+fn dattorro_line(samples: f64) -> DelayLine {
+  DelayLine::new((samples + 2. * DATTORRO_MAX_EXCURSION) / DATTORRO_SAMPLE_RATE)
+}
+
+#[derive(Debug, Clone)]
+struct LatticeAllPass {
+  line: DelayLine,
+}
+
+impl LatticeAllPass {
+  fn process(
+    &mut self,
+    input: f64,
+    gain: f64,
+    delay: f64,
+    interpolation: DelayInterpolation,
+    sample_rate: f64,
+  ) -> f64 {
+    let delayed = self.line.read(delay, interpolation);
+    let w = input - gain * delayed;
+    self.line.push(w, sample_rate);
+    delayed + gain * w
+  }
+}
+
+#[derive(Debug, Clone)]
+struct DattorroTankHalf {
+  lengths: (f64, f64, f64, f64),
+  modulated_all_pass: LatticeAllPass,
+  first_delay: DelayLine,
+  damping_state: f64,
+  all_pass: LatticeAllPass,
+  second_delay: DelayLine,
+  output: f64,
+}
+
+impl DattorroTankHalf {
+  fn new(lengths: (f64, f64, f64, f64)) -> Self {
+    Self {
+      lengths,
+      modulated_all_pass: LatticeAllPass {
+        line: dattorro_line(lengths.0),
+      },
+      first_delay: dattorro_line(lengths.1),
+      damping_state: 0.,
+      all_pass: LatticeAllPass {
+        line: dattorro_line(lengths.2),
+      },
+      second_delay: dattorro_line(lengths.3),
+      output: 0.,
+    }
+  }
+  fn line(&self, index: usize) -> &DelayLine {
+    match index {
+      0 => &self.first_delay,
+      1 => &self.all_pass.line,
+      _ => &self.second_delay,
+    }
+  }
+  fn process(
+    &mut self,
+    input: f64,
+    excursion: f64,
+    decay: f64,
+    decay_diffusion: f64,
+    damping: f64,
+    sample_rate: f64,
+  ) {
+    let (modulated_length, first_length, all_pass_length, second_length) =
+      self.lengths;
+    let x = self.modulated_all_pass.process(
+      input,
+      -DATTORRO_DECAY_DIFFUSION,
+      dattorro_length(modulated_length, sample_rate) + excursion,
+      DelayInterpolation::AllPass,
+      sample_rate,
+    );
+    let delayed = self.first_delay.read(
+      dattorro_length(first_length, sample_rate),
+      DelayInterpolation::Linear,
+    );
+    self.first_delay.push(x, sample_rate);
+    self.damping_state =
+      (1. - damping) * delayed + damping * self.damping_state;
+    let x = self.all_pass.process(
+      decay * self.damping_state,
+      decay_diffusion,
+      dattorro_length(all_pass_length, sample_rate),
+      DelayInterpolation::Linear,
+      sample_rate,
+    );
+    let delayed = self.second_delay.read(
+      dattorro_length(second_length, sample_rate),
+      DelayInterpolation::Linear,
+    );
+    self.second_delay.push(x, sample_rate);
+    self.output = delayed;
+  }
+}
+
+// Decay, damping and bandwidth run from 0 to 1, pre-delay is in seconds and
+// modulation scales the tank all-passes' excursion from 0 to 16 samples
+// (at the original 29.761kHz). As a mono `Signal` it outputs the average of
+// the two channels.
+pub struct Dattorro<
+  De: Signal,
+  Da: Signal,
+  B: Signal,
+  P: Signal,
+  M: Signal,
+  S: Signal,
+> {
+  decay: De,
+  damping: Da,
+  bandwidth: B,
+  pre_delay: P,
+  modulation: M,
+  child: S,
+  pre_delay_line: DelayLine,
+  bandwidth_state: f64,
+  input_diffusers: Vec<LatticeAllPass>,
+  tank: [DattorroTankHalf; 2],
+  lfo_phase: f64,
+  wet: f64,
+  clock: SampleClock,
+}
+derive_signal_ops!(Dattorro<De: Signal, Da: Signal, B: Signal, P: Signal, M: Signal, S: Signal>);
+impl<De: Signal, Da: Signal, B: Signal, P: Signal, M: Signal, S: Signal>
+  Dattorro<De, Da, B, P, M, S>
+{
+  pub fn new(
+    decay: De,
+    damping: Da,
+    bandwidth: B,
+    pre_delay: P,
+    modulation: M,
+    child: S,
+  ) -> Self {
+    Self {
+      decay,
+      damping,
+      bandwidth,
+      pre_delay,
+      modulation,
+      child,
+      pre_delay_line: DelayLine::new(DATTORRO_MAX_PRE_DELAY),
+      bandwidth_state: 0.,
+      input_diffusers: DATTORRO_INPUT_DIFFUSERS
+        .iter()
+        .map(|(length, _)| LatticeAllPass {
+          line: dattorro_line(*length),
+        })
+        .collect(),
+      tank: DATTORRO_TANK_LENGTHS.map(DattorroTankHalf::new),
+      lfo_phase: 0.,
+      wet: 0.5,
+      clock: SampleClock::new(),
+    }
+  }
+  pub fn with_mix(mut self, wet: f64) -> Self {
+    self.wet = wet;
+    self
+  }
+}
+impl<S: Signal> Dattorro<Const, Const, Const, Const, Const, S> {
+  pub fn constant(
+    decay: f64,
+    damping: f64,
+    bandwidth: f64,
+    pre_delay: f64,
+    modulation: f64,
+    child: S,
+  ) -> Self {
+    Self::new(
+      Const(decay),
+      Const(damping),
+      Const(bandwidth),
+      Const(pre_delay),
+      Const(modulation),
+      child,
+    )
+  }
+}
+impl<De: Signal, Da: Signal, B: Signal, P: Signal, M: Signal, S: Signal>
+  StereoSignal for Dattorro<De, Da, B, P, M, S>
+{
+  fn sample_stereo(&mut self, t: f64) -> (f64, f64) {
+    let dt = self.clock.tick(t);
+    let sample_rate = 1. / dt;
+    let decay = self.decay.sample(t).clamp(0., 0.9999);
+    let damping = self.damping.sample(t).clamp(0., 1.);
+    let bandwidth = self.bandwidth.sample(t).clamp(0., 1.);
+    let pre_delay = self.pre_delay.sample(t).max(0.) * sample_rate;
+    let excursion = self.modulation.sample(t).clamp(0., 1.)
+      * DATTORRO_MAX_EXCURSION
+      * sample_rate
+      / DATTORRO_SAMPLE_RATE;
+    let decay_diffusion = (decay + 0.15).clamp(0.25, 0.5);
+    let dry = self.child.sample(t);
+
+    let delayed = self
+      .pre_delay_line
+      .read(pre_delay, DelayInterpolation::Linear);
+    self.pre_delay_line.push(dry, sample_rate);
+    self.bandwidth_state =
+      bandwidth * delayed + (1. - bandwidth) * self.bandwidth_state;
+    let diffused = self
+      .input_diffusers
+      .iter_mut()
+      .zip(DATTORRO_INPUT_DIFFUSERS.iter())
+      .fold(self.bandwidth_state, |x, (diffuser, (length, gain))| {
+        diffuser.process(
+          x,
+          *gain,
+          dattorro_length(*length, sample_rate),
+          DelayInterpolation::Linear,
+          sample_rate,
+        )
+      });
+
+    self.lfo_phase = (self.lfo_phase + DATTORRO_LFO_RATE * dt) % 1.;
+    let lfo = std::f64::consts::TAU * self.lfo_phase;
+    let [left_feedback, right_feedback] =
+      [self.tank[1].output, self.tank[0].output];
+    self.tank[0].process(
+      diffused + decay * left_feedback,
+      excursion * (1. + lfo.sin()),
+      decay,
+      decay_diffusion,
+      damping,
+      sample_rate,
+    );
+    self.tank[1].process(
+      diffused + decay * right_feedback,
+      excursion * (1. + lfo.cos()),
+      decay,
+      decay_diffusion,
+      damping,
+      sample_rate,
+    );
+
+    let [left, right] = DATTORRO_TAPS.map(|taps| {
+      0.6
+        * taps
+          .iter()
+          .map(|(half, line, offset, sign)| {
+            sign
+              * self.tank[*half]
+                .line(*line)
+                .tap(dattorro_length(*offset, sample_rate) as usize)
+          })
+          .sum::<f64>()
+    });
+    (
+      dry * (1. - self.wet) + left * self.wet,
+      dry * (1. - self.wet) + right * self.wet,
+    )
+  }
+}
+impl<De: Signal, Da: Signal, B: Signal, P: Signal, M: Signal, S: Signal> Signal
+  for Dattorro<De, Da, B, P, M, S>
+{
+  fn sample(&mut self, t: f64) -> f64 {
+    let (left, right) = self.sample_stereo(t);
+    0.5 * (left + right)
+  }
+}
@@ -1,4 +1,5 @@
-use crate::{derive_signal_ops, tri, Signal};
+use crate::util::mix;
+use crate::{derive_signal_ops, tri, Signal, Table};
 
 pub struct Wavefold<S: Signal>(pub S);
 derive_signal_ops!(Wavefold<S: Signal>);
@@ -7,3 +8,235 @@ impl<S: Signal> Signal for Wavefold<S> {
     tri(0.25 * self.0.sample(t))
   }
 }
+
+// First-order antiderivative anti-aliasing: outputs the average of the
+// shaping function over the segment between consecutive inputs, which is
+// (F(x) - F(last_x)) / (x - last_x) for an antiderivative F.
+#[derive(Debug, Clone, Default)]
+pub struct AdaaState {
+  last_x: f64,
+}
+
+impl AdaaState {
+  pub fn process<F: Fn(f64) -> f64, A: Fn(f64) -> f64>(
+    &mut self,
+    x: f64,
+    f: F,
+    antiderivative: A,
+  ) -> f64 {
+    let last_x = self.last_x;
+    self.last_x = x;
+    if (x - last_x).abs() < 1e-6 {
+      f(0.5 * (x + last_x))
+    } else {
+      (antiderivative(x) - antiderivative(last_x)) / (x - last_x)
+    }
+  }
+}
+
+fn tri_antiderivative(x: f64) -> f64 {
+  let x = x.rem_euclid(1.);
+  if x < 0.25 {
+    2. * x * x
+  } else if x < 0.75 {
+    2. * x - 2. * x * x - 0.25
+  } else {
+    2. * (x - 1.) * (x - 1.)
+  }
+}
+
+pub struct WavefoldAdaa<S: Signal>(pub S, AdaaState);
+derive_signal_ops!(WavefoldAdaa<S: Signal>);
+impl<S: Signal> WavefoldAdaa<S> {
+  pub fn new(child: S) -> Self {
+    Self(child, AdaaState::default())
+  }
+}
+impl<S: Signal> Signal for WavefoldAdaa<S> {
+  fn sample(&mut self, t: f64) -> f64 {
+    let x = self.0.sample(t);
+    self
+      .1
+      .process(x, |x| tri(0.25 * x), |x| 4. * tri_antiderivative(0.25 * x))
+  }
+}
+
+const BUCHLA_FOLD_SPACING: f64 = 1.2;
+
+// Parallel folding cells with evenly spaced thresholds, each of which reverses
+// the slope of the transfer curve once the input passes it, as in the Buchla
+// 259 timbre circuit. `fold` runs from 0 (no folding) to 1 (drives the input
+// through every stage).
+pub struct BuchlaFold<A: Signal, S: Signal> {
+  fold: A,
+  child: S,
+  stages: usize,
+}
+derive_signal_ops!(BuchlaFold<A: Signal, S: Signal>);
+impl<A: Signal, S: Signal> BuchlaFold<A, S> {
+  pub fn new(stages: usize, fold: A, child: S) -> Self {
+    Self {
+      fold,
+      child,
+      stages,
+    }
+  }
+}
+impl<A: Signal, S: Signal> Signal for BuchlaFold<A, S> {
+  fn sample(&mut self, t: f64) -> f64 {
+    let first_threshold = 0.5 * BUCHLA_FOLD_SPACING;
+    let drive = 1.
+      + self.fold.sample(t).clamp(0., 1.)
+        * self.stages as f64
+        * BUCHLA_FOLD_SPACING
+        / first_threshold;
+    let x = drive * first_threshold * self.child.sample(t);
+    let folded = (0..self.stages).fold(x, |y, stage| {
+      let threshold = first_threshold + stage as f64 * BUCHLA_FOLD_SPACING;
+      let sign = if stage % 2 == 0 { 2. } else { -2. };
+      y - sign * x.signum() * (x.abs() - threshold).max(0.)
+    });
+    (folded / first_threshold).tanh()
+  }
+}
+
+// Sums Chebyshev polynomials of the first kind, so that a full-scale sine
+// input gets exactly the harmonic amplitudes given in `weights` (starting at
+// the fundamental).
+#[derive(Debug, Clone)]
+pub struct Chebyshev<S: Signal> {
+  weights: Vec<f64>,
+  child: S,
+}
+derive_signal_ops!(Chebyshev<S: Signal>);
+impl<S: Signal> Chebyshev<S> {
+  pub fn new(weights: Vec<f64>, child: S) -> Self {
+    Self { weights, child }
+  }
+}
+impl<S: Signal> Signal for Chebyshev<S> {
+  fn sample(&mut self, t: f64) -> f64 {
+    let x = self.child.sample(t).clamp(-1., 1.);
+    let (mut previous, mut current) = (1., x);
+    let mut sum = 0.;
+    for weight in self.weights.iter() {
+      sum += weight * current;
+      (previous, current) = (current, 2. * x * current - previous);
+    }
+    sum
+  }
+}
+
+// Asymmetric exponential clipping, with the negative half saturating more
+// softly and at a higher level than the positive half, like a diode pair
+// with mismatched forward voltages.
+#[derive(Debug, Clone)]
+pub struct DiodeClip<S: Signal>(pub S);
+derive_signal_ops!(DiodeClip<S: Signal>);
+impl<S: Signal> Signal for DiodeClip<S> {
+  fn sample(&mut self, t: f64) -> f64 {
+    let x = self.0.sample(t);
+    if x >= 0. {
+      1. - (-x).exp()
+    } else {
+      2. * ((0.5 * x).exp() - 1.)
+    }
+  }
+}
+
+// Biased tanh saturation. A nonzero bias makes the curve asymmetric and adds
+// even harmonics, like an overdriven triode; the bias offset is removed so
+// silence stays at zero.
+#[derive(Debug, Clone)]
+pub struct TubeClip<B: Signal, S: Signal>(pub B, pub S);
+derive_signal_ops!(TubeClip<B: Signal, S: Signal>);
+impl<B: Signal, S: Signal> Signal for TubeClip<B, S> {
+  fn sample(&mut self, t: f64) -> f64 {
+    let bias = self.0.sample(t);
+    (self.1.sample(t) + bias).tanh() - bias.tanh()
+  }
+}
+
+// Clips at `threshold`, easing into it with a quadratic curve over a region
+// of width `knee` around the threshold. A knee of 0 gives hard clipping.
+#[derive(Debug, Clone)]
+pub struct KneeClip<C: Signal, K: Signal, S: Signal>(pub C, pub K, pub S);
+derive_signal_ops!(KneeClip<C: Signal, K: Signal, S: Signal>);
+impl<C: Signal, K: Signal, S: Signal> Signal for KneeClip<C, K, S> {
+  fn sample(&mut self, t: f64) -> f64 {
+    let threshold = self.0.sample(t).abs();
+    let knee = self.1.sample(t).abs().min(2. * threshold);
+    let x = self.2.sample(t);
+    let magnitude = x.abs();
+    let clipped = if magnitude <= threshold - 0.5 * knee {
+      magnitude
+    } else if magnitude >= threshold + 0.5 * knee {
+      threshold
+    } else {
+      magnitude - (magnitude - threshold + 0.5 * knee).powi(2) / (2. * knee)
+    };
+    x.signum() * clipped
+  }
+}
+
+// Maps the input through an arbitrary transfer curve. Table based curves
+// spread their values evenly over inputs from -1 to 1, while breakpoint
+// curves interpolate between (input, output) pairs. Inputs outside the curve
+// hold its end values.
+#[derive(Debug, Clone)]
+pub enum TransferCurve {
+  Table(Vec<f64>),
+  Breakpoints(Vec<(f64, f64)>),
+}
+
+impl TransferCurve {
+  pub fn evaluate(&self, x: f64) -> f64 {
+    match self {
+      TransferCurve::Table(values) => {
+        if values.is_empty() {
+          return x;
+        }
+        let position =
+          (0.5 * (x + 1.)).clamp(0., 1.) * (values.len() - 1) as f64;
+        let index = (position.floor() as usize).min(values.len() - 1);
+        let next = (index + 1).min(values.len() - 1);
+        mix(values[index], values[next], position - index as f64)
+      }
+      TransferCurve::Breakpoints(points) => {
+        match points.iter().position(|(input, _)| *input > x) {
+          None => points.last().map(|(_, output)| *output).unwrap_or(x),
+          Some(0) => points[0].1,
+          Some(i) => {
+            let (x_1, y_1) = points[i - 1];
+            let (x_2, y_2) = points[i];
+            mix(y_1, y_2, (x - x_1) / (x_2 - x_1))
+          }
+        }
+      }
+    }
+  }
+}
+
+#[derive(Debug, Clone)]
+pub struct Shaper<S: Signal> {
+  curve: TransferCurve,
+  child: S,
+}
+derive_signal_ops!(Shaper<S: Signal>);
+impl<S: Signal> Shaper<S> {
+  pub fn new(curve: TransferCurve, child: S) -> Self {
+    Self { curve, child }
+  }
+  pub fn from_table<T: Signal>(table: &Table<T>, child: S) -> Self {
+    Self::new(TransferCurve::Table(table.values().to_vec()), child)
+  }
+  pub fn from_breakpoints(mut points: Vec<(f64, f64)>, child: S) -> Self {
+    points.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+    Self::new(TransferCurve::Breakpoints(points), child)
+  }
+}
+impl<S: Signal> Signal for Shaper<S> {
+  fn sample(&mut self, t: f64) -> f64 {
+    self.curve.evaluate(self.child.sample(t))
+  }
+}
@@ -0,0 +1,166 @@
+use crate::util::{mix, one_pole_coefficient, SampleClock};
+use crate::{derive_signal_ops, Const, Signal};
+
+// Limits how fast the child can change, with separate maximum rise and fall
+// rates in units per second. Starts at the child's first value.
+pub struct Slew<R: Signal, F: Signal, S: Signal> {
+  rise: R,
+  fall: F,
+  child: S,
+  clock: SampleClock,
+  value: Option<f64>,
+}
+derive_signal_ops!(Slew<R: Signal, F: Signal, S: Signal>);
+impl<R: Signal, F: Signal, S: Signal> Slew<R, F, S> {
+  pub fn new(rise: R, fall: F, child: S) -> Self {
+    Self {
+      rise,
+      fall,
+      child,
+      clock: SampleClock::new(),
+      value: None,
+    }
+  }
+}
+impl<S: Signal> Slew<Const, Const, S> {
+  pub fn constant(rise: f64, fall: f64, child: S) -> Self {
+    Self::new(Const(rise), Const(fall), child)
+  }
+}
+impl<R: Signal, F: Signal, S: Signal> Signal for Slew<R, F, S> {
+  fn sample(&mut self, t: f64) -> f64 {
+    let dt = self.clock.tick(t);
+    let target = self.child.sample(t);
+    let rise = self.rise.sample(t).max(0.);
+    let fall = self.fall.sample(t).max(0.);
+    let value = match self.value {
+      Some(value) => value + (target - value).clamp(-fall * dt, rise * dt),
+      None => target,
+    };
+    self.value = Some(value);
+    value
+  }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum GlideMode {
+  // every change takes `time` seconds, however far it goes
+  ConstantTime,
+  // changes move at one octave per `time` seconds
+  ConstantRate,
+}
+
+// Portamento for a frequency signal. Glides happen in log-frequency, so they
+// sound even across the whole range.
+pub struct Glide<T: Signal, S: Signal> {
+  time: T,
+  child: S,
+  mode: GlideMode,
+  clock: SampleClock,
+  // current, start and target pitches in octaves
+  pitch: Option<f64>,
+  from: f64,
+  target: f64,
+  progress: f64,
+}
+derive_signal_ops!(Glide<T: Signal, S: Signal>);
+impl<T: Signal, S: Signal> Glide<T, S> {
+  pub fn new(time: T, child: S) -> Self {
+    Self {
+      time,
+      child,
+      mode: GlideMode::ConstantTime,
+      clock: SampleClock::new(),
+      pitch: None,
+      from: 0.,
+      target: 0.,
+      progress: 1.,
+    }
+  }
+  pub fn with_mode(mut self, mode: GlideMode) -> Self {
+    self.mode = mode;
+    self
+  }
+}
+impl<S: Signal> Glide<Const, S> {
+  pub fn constant(time: f64, child: S) -> Self {
+    Self::new(Const(time), child)
+  }
+}
+impl<T: Signal, S: Signal> Signal for Glide<T, S> {
+  fn sample(&mut self, t: f64) -> f64 {
+    let dt = self.clock.tick(t);
+    let target = self.child.sample(t).max(f64::MIN_POSITIVE).log2();
+    let time = self.time.sample(t).max(0.);
+    let pitch = match self.pitch {
+      None => {
+        self.target = target;
+        target
+      }
+      Some(pitch) => match self.mode {
+        GlideMode::ConstantTime => {
+          if target != self.target {
+            self.from = pitch;
+            self.target = target;
+            self.progress = 0.;
+          }
+          self.progress = if time > 0. {
+            (self.progress + dt / time).min(1.)
+          } else {
+            1.
+          };
+          mix(self.from, self.target, self.progress)
+        }
+        GlideMode::ConstantRate => {
+          if time > 0. {
+            let step = dt / time;
+            pitch + (target - pitch).clamp(-step, step)
+          } else {
+            target
+          }
+        }
+      },
+    };
+    self.pitch = Some(pitch);
+    pitch.exp2()
+  }
+}
+
+// A one-pole smoother for control signals such as MIDI values, with its time
+// constant in milliseconds. Starts at the child's first value.
+pub struct Smooth<T: Signal, S: Signal> {
+  time_ms: T,
+  child: S,
+  clock: SampleClock,
+  value: Option<f64>,
+}
+derive_signal_ops!(Smooth<T: Signal, S: Signal>);
+impl<T: Signal, S: Signal> Smooth<T, S> {
+  pub fn new(time_ms: T, child: S) -> Self {
+    Self {
+      time_ms,
+      child,
+      clock: SampleClock::new(),
+      value: None,
+    }
+  }
+}
+impl<S: Signal> Smooth<Const, S> {
+  pub fn constant(time_ms: f64, child: S) -> Self {
+    Self::new(Const(time_ms), child)
+  }
+}
+impl<T: Signal, S: Signal> Signal for Smooth<T, S> {
+  fn sample(&mut self, t: f64) -> f64 {
+    let dt = self.clock.tick(t);
+    let target = self.child.sample(t);
+    let coefficient =
+      one_pole_coefficient(self.time_ms.sample(t).max(0.) * 0.001, dt);
+    let value = match self.value {
+      Some(value) => mix(target, value, coefficient),
+      None => target,
+    };
+    self.value = Some(value);
+    value
+  }
+}
@@ -0,0 +1,95 @@
+use crate::util::{is_plausible_sample_rate, SampleClock};
+use crate::{
+  derive_signal_ops, BiquadCoefficients, BiquadState, DetectorMode,
+  LevelDetector, Signal,
+};
+
+struct VocoderBand {
+  frequency: f64,
+  coefficients: BiquadCoefficients,
+  modulator_filter: BiquadState,
+  carrier_filter: BiquadState,
+  detector: LevelDetector,
+}
+
+// Imposes the spectral envelope of `modulator` onto `carrier`, using
+// logarithmically spaced band-pass filters over the frequency range.
+pub struct Vocoder<M: Signal, C: Signal> {
+  modulator: M,
+  carrier: C,
+  bands: Vec<VocoderBand>,
+  q: f64,
+  sample_rate: f64,
+  clock: SampleClock,
+}
+derive_signal_ops!(Vocoder<M: Signal, C: Signal>);
+impl<M: Signal, C: Signal> Vocoder<M, C> {
+  pub fn new(band_count: usize, modulator: M, carrier: C) -> Self {
+    Self {
+      modulator,
+      carrier,
+      bands: (0..band_count)
+        .map(|_| VocoderBand {
+          frequency: 0.,
+          coefficients: BiquadCoefficients::identity(),
+          modulator_filter: BiquadState::new(),
+          carrier_filter: BiquadState::new(),
+          detector: LevelDetector::new(DetectorMode::Peak, 0.005, 0.05),
+        })
+        .collect(),
+      q: 6.,
+      sample_rate: 0.,
+      clock: SampleClock::new(),
+    }
+    .with_range(100., 8000.)
+  }
+  pub fn with_range(mut self, low_frequency: f64, high_frequency: f64) -> Self {
+    let ratio = high_frequency / low_frequency;
+    let steps = (self.bands.len().max(2) - 1) as f64;
+    for (index, band) in self.bands.iter_mut().enumerate() {
+      band.frequency = low_frequency * ratio.powf(index as f64 / steps);
+    }
+    self.sample_rate = 0.;
+    self
+  }
+  pub fn with_q(mut self, q: f64) -> Self {
+    self.q = q;
+    self.sample_rate = 0.;
+    self
+  }
+  pub fn with_envelope(mut self, attack: f64, release: f64) -> Self {
+    for band in self.bands.iter_mut() {
+      band.detector.attack = attack;
+      band.detector.release = release;
+    }
+    self
+  }
+}
+impl<M: Signal, C: Signal> Signal for Vocoder<M, C> {
+  fn sample(&mut self, t: f64) -> f64 {
+    let dt = self.clock.tick(t);
+    let sample_rate = 1. / dt;
+    if is_plausible_sample_rate(sample_rate)
+      && (sample_rate - self.sample_rate).abs() > 1.
+    {
+      self.sample_rate = sample_rate;
+      for band in self.bands.iter_mut() {
+        band.coefficients =
+          BiquadCoefficients::band_pass(band.frequency, self.q, sample_rate);
+      }
+    }
+    let modulator = self.modulator.sample(t);
+    let carrier = self.carrier.sample(t);
+    self
+      .bands
+      .iter_mut()
+      .map(|band| {
+        let level = band.detector.process(
+          band.modulator_filter.process(&band.coefficients, modulator),
+          dt,
+        );
+        level * band.carrier_filter.process(&band.coefficients, carrier)
+      })
+      .sum()
+  }
+}
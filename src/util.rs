@@ -1,3 +1,85 @@
 pub fn mix(a: f64, b: f64, p: f64) -> f64 {
   a * (1.0 - p) + b * p
 }
+
+pub fn db_to_amplitude(db: f64) -> f64 {
+  10f64.powf(db / 20.)
+}
+
+pub fn amplitude_to_db(amplitude: f64) -> f64 {
+  20. * amplitude.abs().max(1e-12).log10()
+}
+
+pub const DEFAULT_SAMPLE_RATE: f64 = 44100.;
+
+// Sample periods outside this range are treated as gaps in sampling (e.g. a
+// voice that stopped being sampled for a while) rather than as a rate.
+pub const MIN_SAMPLE_RATE: f64 = 1000.;
+pub const MAX_SAMPLE_RATE: f64 = 1536000.;
+
+pub fn is_plausible_sample_rate(sample_rate: f64) -> bool {
+  (MIN_SAMPLE_RATE..=MAX_SAMPLE_RATE).contains(&sample_rate)
+}
+
+// Signals only ever see `t`, so nodes that need the sample period infer it
+// from the spacing between consecutive calls. Until a plausible spacing has
+// been seen, the period of `DEFAULT_SAMPLE_RATE` is used.
+#[derive(Debug, Clone)]
+pub struct SampleClock {
+  last_t: Option<f64>,
+  dt: f64,
+}
+
+impl SampleClock {
+  pub fn new() -> Self {
+    Self {
+      last_t: None,
+      dt: 1. / DEFAULT_SAMPLE_RATE,
+    }
+  }
+  pub fn tick(&mut self, t: f64) -> f64 {
+    if let Some(last_t) = self.last_t {
+      let dt = t - last_t;
+      if dt > 0. && is_plausible_sample_rate(1. / dt) {
+        self.dt = dt;
+      }
+    }
+    self.last_t = Some(t);
+    self.dt
+  }
+  pub fn dt(&self) -> f64 {
+    self.dt
+  }
+  pub fn sample_rate(&self) -> f64 {
+    1. / self.dt
+  }
+}
+
+pub fn resample(samples: &[f64], from_rate: f64, to_rate: f64) -> Vec<f64> {
+  if samples.is_empty() || from_rate == to_rate {
+    return samples.to_vec();
+  }
+  let length = ((samples.len() as f64) * to_rate / from_rate).ceil() as usize;
+  (0..length)
+    .map(|i| {
+      let position = i as f64 * from_rate / to_rate;
+      let index = position.floor() as usize;
+      let next = (index + 1).min(samples.len() - 1);
+      mix(
+        samples[index.min(samples.len() - 1)],
+        samples[next],
+        position % 1.,
+      )
+    })
+    .collect()
+}
+
+// Per-sample smoothing coefficient for a one-pole filter with the given time
+// constant in seconds.
+pub fn one_pole_coefficient(time: f64, dt: f64) -> f64 {
+  if time > 0. {
+    (-dt / time).exp()
+  } else {
+    0.
+  }
+}
@@ -0,0 +1,36 @@
+use anyhow::Result;
+use hound::{SampleFormat, WavReader};
+use std::path::Path;
+
+#[derive(Debug, Clone)]
+pub struct WavData {
+  pub samples: Vec<f64>,
+  pub sample_rate: f64,
+}
+
+// Multichannel files are mixed down to mono.
+pub fn load_wav<P: AsRef<Path>>(path: P) -> Result<WavData> {
+  let mut reader = WavReader::open(path)?;
+  let spec = reader.spec();
+  let channels = spec.channels as usize;
+  let interleaved: Vec<f64> = match spec.sample_format {
+    SampleFormat::Float => reader
+      .samples::<f32>()
+      .map(|sample| sample.map(|x| x as f64))
+      .collect::<Result<_, _>>()?,
+    SampleFormat::Int => {
+      let scale = (1i64 << (spec.bits_per_sample - 1)) as f64;
+      reader
+        .samples::<i32>()
+        .map(|sample| sample.map(|x| x as f64 / scale))
+        .collect::<Result<_, _>>()?
+    }
+  };
+  Ok(WavData {
+    samples: interleaved
+      .chunks(channels)
+      .map(|frame| frame.iter().sum::<f64>() / channels as f64)
+      .collect(),
+    sample_rate: spec.sample_rate as f64,
+  })
+}